@@ -1,21 +1,70 @@
+use crate::archive::{absolute_paths, archives_in, ARCHIVE_EXTENSIONS};
+use crate::backend::{BackendKind, ExtractionBackend};
+use crate::backup::{
+    create_backup, diff_passwords, list_backups, write_atomic, PasswordDiff, BACKUP_COUNT,
+};
+use crate::codepage::LegacyEncoding;
+use crate::fingerprint::ArchiveIndex;
+use crate::fonts::install_fonts;
+use crate::harvest::{default_rules, HarvestRule, HarvestSource};
+use crate::import_wizard::ImportWizard;
+use crate::instance::Listener;
+use crate::password_editor::PasswordEditor;
+use crate::passwords::{
+    merge_dictionaries, prefer_tagged, sidecar_path_for, Dedup, Dictionary, DictionaryFormat,
+    Normalization, SanitizeRules,
+};
+use crate::probe::{find_seven_zip, probe_seven_zip, SevenZipInfo};
+use crate::rules::expand_destination;
+use crate::secret::SecretString;
+use crate::usage::{TrialOrder, UsageStore};
+use crate::vault::{is_vault, VaultContents};
 use crate::worker::TaskDisplayer;
 use crate::worker::{Showcase, Task};
 use egui::{Align2, Color32, DroppedFile, Id, LayerId, Order, TextStyle, Vec2};
 use log::debug;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Write;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
 use std::{
     fs,
     path::{Path, PathBuf},
 };
-use wpass::{WPass, WPassInstance};
+
+mod backups;
+mod dictionaries;
+mod extraction;
+mod headless;
+mod routing;
+mod vault_settings;
+mod watch_folders;
+
+use backups::BackupView;
+use dictionaries::{DictionarySource, DropSelection, NewDictionary};
+use extraction::ExtractionJob;
+pub use extraction::FinishedExtraction;
+use routing::{RoutingEditor, RoutingRule};
+use vault_settings::VaultState;
+use watch_folders::{WatchFolder, WatchFolders};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 enum ExtractionMode {
     Local,
     NewDirectory,
+    /// A destination path with `{dir}`, `{stem}` and `{name}` placeholders.
+    Template(String),
 }
 
+const DEFAULT_TEMPLATE: &str = "{dir}/{stem}";
+const TEMPLATE_HELP: &str = "{dir}, {stem} and {name} are replaced with the archive's directory, \
+name without extension and file name. Rules can also use the named groups of their regex.";
+/// How often the window checks whether probing the 7z executable finished.
+const PROBE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long usage recorded in a vault may wait before the vault is sealed again.
+const VAULT_SAVE_DELAY: Duration = Duration::from_secs(30);
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 enum MenuState {
     Main,
@@ -23,28 +72,159 @@ enum MenuState {
     Password,
 }
 
+/// Settings that must be filled in before anything can be extracted.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum RequiredSetting {
+    PasswordFile,
+    ArchiveExecutable,
+    UnrarExecutable,
+    BsdtarExecutable,
+    MasterPassword,
+}
+
+impl RequiredSetting {
+    fn description(&self) -> &'static str {
+        match self {
+            RequiredSetting::PasswordFile => "password file",
+            RequiredSetting::ArchiveExecutable => "7z path",
+            RequiredSetting::UnrarExecutable => "unrar path",
+            RequiredSetting::BsdtarExecutable => "bsdtar path",
+            RequiredSetting::MasterPassword => "master password",
+        }
+    }
+
+    /// Id of the text field in the settings grid, or in the unlock prompt.
+    fn id(&self) -> Id {
+        match self {
+            RequiredSetting::PasswordFile => Id::new("password_file_path"),
+            RequiredSetting::ArchiveExecutable => Id::new("archive_executable_path"),
+            RequiredSetting::UnrarExecutable => Id::new("unrar_executable_path"),
+            RequiredSetting::BsdtarExecutable => Id::new("bsdtar_executable_path"),
+            RequiredSetting::MasterPassword => Id::new("master_password"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)] // new fields must not invalidate a previously saved config
 struct AppConfig {
+    /// The dictionary edited in the password view, also part of `dictionaries`.
     password_file_path: String,
+    /// Tried in this order; the first dictionary containing a password wins.
+    dictionaries: Vec<DictionarySource>,
+    ask_dictionaries_on_drop: bool,
     archive_executable_path: String,
     extraction_mode: ExtractionMode,
     delete_after_extract: bool,
+    /// Writes the password that worked next to the extracted files.
+    write_password_sidecar: bool,
+    /// Renames extracted files whose names are in a detectable legacy codepage.
+    fix_garbled_names: bool,
     sanitize: bool,
+    watch_folders: Vec<WatchFolder>,
+    watch_paused: bool,
+    unrar_executable_path: String,
+    bsdtar_executable_path: String,
+    /// Backend used for each archive extension, 7z when the extension is not listed.
+    format_backends: BTreeMap<String, BackendKind>,
+    /// Codepages ZIP passwords are re-encoded into, after UTF-8.
+    zip_encodings: Vec<LegacyEncoding>,
+    trial_order: TrialOrder,
+    /// Tried before the dictionaries.
+    harvest_rules: Vec<HarvestRule>,
+    sanitize_rules: SanitizeRules,
+    confirm_sanitize: bool,
+    routing_rules: Vec<RoutingRule>,
+    /// Preferred over the default and system fonts; empty for none.
+    custom_font_path: String,
+    /// Named groups of the routing rule that matched, for the destination template.
+    #[serde(skip)]
+    template_captures: BTreeMap<String, String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            password_file_path: String::new(),
+            dictionaries: Vec::new(),
+            ask_dictionaries_on_drop: false,
+            archive_executable_path: String::new(),
+            extraction_mode: ExtractionMode::Local,
+            delete_after_extract: false,
+            write_password_sidecar: false,
+            fix_garbled_names: true,
+            sanitize: true,
+            watch_folders: Vec::new(),
+            watch_paused: false,
+            unrar_executable_path: String::new(),
+            bsdtar_executable_path: String::new(),
+            format_backends: BTreeMap::new(),
+            zip_encodings: Vec::new(),
+            trial_order: TrialOrder::FileOrder,
+            harvest_rules: default_rules(),
+            sanitize_rules: SanitizeRules::default(),
+            confirm_sanitize: true,
+            routing_rules: Vec::new(),
+            custom_font_path: String::new(),
+            template_captures: BTreeMap::new(),
+        }
+    }
 }
 
 impl AppConfig {
-    pub fn calculate_output_path_for(&self, path: &PathBuf) -> PathBuf {
-        match self.extraction_mode {
+    fn backend_kind_for(&self, path: &Path) -> BackendKind {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.format_backends.get(&ext.to_lowercase()))
+            .copied()
+            .unwrap_or(BackendKind::SevenZip)
+    }
+
+    fn executable_for(&self, kind: BackendKind) -> &str {
+        match kind {
+            BackendKind::SevenZip => &self.archive_executable_path,
+            BackendKind::Unrar => &self.unrar_executable_path,
+            BackendKind::Bsdtar => &self.bsdtar_executable_path,
+            BackendKind::Zip => "",
+        }
+    }
+
+    pub fn backend_for(&self, path: &Path) -> Box<dyn ExtractionBackend> {
+        let kind = self.backend_kind_for(path);
+        kind.create(self.executable_for(kind).into(), self.zip_encodings.clone())
+    }
+
+    /// The backends some archive extension is set to use.
+    fn used_backends(&self) -> Vec<BackendKind> {
+        BackendKind::ALL
+            .into_iter()
+            .filter(|kind| {
+                ARCHIVE_EXTENSIONS.iter().any(|extension| {
+                    self.format_backends
+                        .get(*extension)
+                        .copied()
+                        .unwrap_or(BackendKind::SevenZip)
+                        == *kind
+                })
+            })
+            .collect()
+    }
+
+    pub fn calculate_output_path_for(&self, path: &Path) -> PathBuf {
+        match &self.extraction_mode {
             ExtractionMode::Local => {
-                let mut output_path = path.clone();
+                let mut output_path = path.to_path_buf();
                 output_path.pop();
                 if !output_path.is_dir() {
                     output_path.push(".");
                 }
                 output_path
             }
+            ExtractionMode::Template(template) => {
+                expand_destination(template, path, &self.template_captures)
+            }
             ExtractionMode::NewDirectory => {
-                let mut output_path = path.clone().with_extension("");
+                let mut output_path = path.with_extension("");
                 if output_path.exists() {
                     output_path.pop();
                     output_path.push(format!(
@@ -66,24 +246,115 @@ pub struct WPassApp {
     #[serde(skip)]
     menu_state: MenuState,
     #[serde(skip)]
-    passwords: Option<String>,
+    passwords: Option<Dictionary>,
+    /// Enabled dictionaries other than the password file, keyed by path.
+    #[serde(skip)]
+    other_dictionaries: BTreeMap<String, Dictionary>,
+    #[serde(skip)]
+    drop_selection: Option<DropSelection>,
+    /// Modification time and size of the password file when we last read or wrote it.
+    #[serde(skip)]
+    password_file_stamp: Option<(SystemTime, u64)>,
+    /// The path `password_file_stamp` belongs to; differs while the path is being edited.
+    #[serde(skip)]
+    stamped_password_file: String,
+    /// The passwords and their metadata as they were last read from or
+    /// written to disk, to tell local edits apart from changes made by someone else.
+    #[serde(skip)]
+    saved_passwords: Option<Dictionary>,
+    /// The on-disk version, while both it and the local copy have changed.
+    #[serde(skip)]
+    password_conflict: Option<Dictionary>,
+    #[serde(skip)]
+    last_password_check: Option<Instant>,
+    /// When usage first went unsaved. Sealing the vault is slow, so usage is
+    /// written to it in batches rather than after every extraction.
+    #[serde(skip)]
+    usage_unsaved_since: Option<Instant>,
+    #[serde(skip)]
+    backup_view: Option<BackupView>,
+    #[serde(skip)]
+    new_dictionary: Option<NewDictionary>,
+    #[serde(skip)]
+    import_wizard: Option<ImportWizard>,
+    #[serde(skip)]
+    routing_editor: RoutingEditor,
+    /// The password list as sanitizing would leave it, until the user confirms.
+    #[serde(skip)]
+    sanitize_preview: Option<Dictionary>,
+    /// The passwords as they were when the user chose to keep them unsanitized,
+    /// so the preview does not come back until they change.
+    #[serde(skip)]
+    sanitize_declined: Option<Dictionary>,
+    /// The last failure to read or write a file, until dismissed.
+    #[serde(skip)]
+    error_message: Option<String>,
+    /// Harvested passwords that opened an archive but are in no dictionary.
+    #[serde(skip)]
+    harvested_suggestions: Vec<SecretString>,
+    #[serde(skip)]
+    password_editor: PasswordEditor,
+    #[serde(skip)]
+    usage: UsageStore,
+    #[serde(skip)]
+    vault: VaultState,
+    /// The password that opened each archive, by fingerprint.
+    #[serde(skip)]
+    archives: ArchiveIndex,
+    #[serde(skip)]
+    task_showcase: Showcase<FinishedExtraction>,
     #[serde(skip)]
-    task_showcase: Showcase<Vec<PathBuf>>,
+    watch_folders: WatchFolders,
+    /// Result of running the configured 7z executable, `None` until it has been checked.
+    #[serde(skip)]
+    seven_zip_probe: Option<Task<SevenZipInfo>>,
+    /// The path `seven_zip_probe` ran, which the setting may have moved away from.
+    #[serde(skip)]
+    probed_executable: String,
+    /// Files received while the configuration was incomplete.
+    #[serde(skip)]
+    pending_files: Vec<PathBuf>,
+    /// Files of later launches, which hand them to this window.
+    #[serde(skip)]
+    launches: Option<mpsc::Receiver<Vec<PathBuf>>>,
+    /// Settings field to focus the next time the settings are shown.
+    #[serde(skip)]
+    focus_setting: Option<RequiredSetting>,
 }
 
 impl Default for WPassApp {
     fn default() -> Self {
         Self {
-            config: AppConfig {
-                password_file_path: String::new(),
-                archive_executable_path: String::new(),
-                extraction_mode: ExtractionMode::Local,
-                delete_after_extract: false,
-                sanitize: true,
-            },
+            config: AppConfig::default(),
             menu_state: MenuState::Main,
             passwords: None,
+            other_dictionaries: BTreeMap::new(),
+            drop_selection: None,
+            password_file_stamp: None,
+            stamped_password_file: String::new(),
+            saved_passwords: None,
+            password_conflict: None,
+            last_password_check: None,
+            usage_unsaved_since: None,
+            backup_view: None,
+            new_dictionary: None,
+            import_wizard: None,
+            routing_editor: RoutingEditor::default(),
+            sanitize_preview: None,
+            sanitize_declined: None,
+            error_message: None,
+            harvested_suggestions: Vec::new(),
+            password_editor: PasswordEditor::new(),
+            usage: UsageStore::new(),
+            vault: VaultState::default(),
+            archives: ArchiveIndex::new(),
             task_showcase: Showcase::new(),
+            watch_folders: WatchFolders::new(),
+            seven_zip_probe: None,
+            probed_executable: String::new(),
+            pending_files: Vec::new(),
+            launches: None,
+            focus_setting: None,
         }
     }
 }
@@ -91,115 +362,934 @@ impl Default for WPassApp {
 impl WPassApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // This is also where you can customize the look and feel of egui using
-        // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
-
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
-        }
+        let mut app: Self = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        // Archive names are often Chinese, Japanese or Korean, which the
+        // default fonts cannot show.
+        app.update_fonts(&cc.egui_ctx);
+        app
+    }
 
-        Default::default()
+    fn update_fonts(&mut self, ctx: &egui::Context) {
+        let custom = Some(PathBuf::from(&self.config.custom_font_path))
+            .filter(|path| !path.as_os_str().is_empty());
+        if let Err(e) = install_fonts(ctx, custom.as_deref()) {
+            self.report_error("Could not load the custom font", e);
+        }
     }
 
     pub fn init(&mut self) {
         debug!("Initializing app");
         self.update_passwords_from_file();
+        if self.config.archive_executable_path.is_empty() {
+            if let Some(path) = find_seven_zip() {
+                debug!("Found 7z executable at {:?}", path);
+                self.config.archive_executable_path = path.display().to_string();
+            }
+        }
+        self.validate_archive_executable();
         debug!("App initialized: {:?}", self);
     }
 
     fn update_passwords_from_file(&mut self) {
         debug!("Updating passwords");
+        if self.usage_unsaved_since.take().is_some() {
+            debug!("Dropping usage not yet saved to the vault");
+        }
         let password_file_pathbuf = PathBuf::from(&self.config.password_file_path);
-        if Path::is_file(&password_file_pathbuf) {
-            self.passwords = Some(fs::read_to_string(&password_file_pathbuf).unwrap());
+        if Path::is_file(&password_file_pathbuf) && is_vault(&password_file_pathbuf) {
+            // Stay unlocked if the vault is still sealed with the key we have.
+            let reopened = self
+                .vault
+                .key
+                .as_ref()
+                .and_then(|key| key.reopen(&fs::read(&password_file_pathbuf).ok()?).ok());
+            match reopened {
+                Some(contents) => self.use_vault_contents(contents),
+                None => {
+                    self.vault.key = None;
+                    self.vault.locked = true;
+                    self.passwords = None;
+                    self.usage = UsageStore::new();
+                    self.archives = ArchiveIndex::new();
+                }
+            }
+        } else if Path::is_file(&password_file_pathbuf) {
+            self.vault = VaultState::default();
+            self.passwords = match Dictionary::load(&password_file_pathbuf) {
+                Ok(passwords) => Some(passwords),
+                Err(e) => {
+                    self.report_error("Could not read the password file", e);
+                    None
+                }
+            };
+            self.usage = UsageStore::load(&self.usage_path());
+            self.archives = ArchiveIndex::load(&self.archives_path());
         } else {
+            self.vault = VaultState::default();
             self.passwords = None;
+            self.usage = UsageStore::new();
+            self.archives = ArchiveIndex::new();
+        }
+        self.password_editor.clear_selection();
+        self.password_conflict = None;
+        self.mark_passwords_saved();
+        debug!(
+            "Passwords updated, {} entries",
+            self.passwords.as_ref().map_or(0, |p| p.entries.len())
+        );
+        self.update_other_dictionaries();
+    }
+
+    fn write_password_file(&self) -> anyhow::Result<()> {
+        let password_file_pathbuf = PathBuf::from(&self.config.password_file_path);
+        if let Some(passwords) = &self.passwords {
+            if Path::is_file(&password_file_pathbuf) {
+                match &self.vault.key {
+                    Some(key) => {
+                        let contents = VaultContents {
+                            passwords: passwords.to_text(),
+                            metadata: passwords.metadata(),
+                            usage: self.usage.clone(),
+                            archives: self.archives.clone(),
+                        };
+                        write_atomic(&password_file_pathbuf, &key.seal(&contents)?)?;
+                    }
+                    None => {
+                        // What we cannot read may hold metadata that saving would drop.
+                        if !is_vault(&password_file_pathbuf) {
+                            Dictionary::load(&password_file_pathbuf).map_err(|e| {
+                                anyhow::anyhow!("Not replacing a file that cannot be read: {}", e)
+                            })?;
+                        }
+                        passwords.save(&password_file_pathbuf)?
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remembers the current passwords as the version that is on disk.
+    fn mark_passwords_saved(&mut self) {
+        self.mark_password_file_stamp();
+        self.saved_passwords = self.passwords.clone();
+    }
+
+    /// Remembers the file as it is now, so our own write is not taken for a change.
+    fn mark_password_file_stamp(&mut self) {
+        self.password_file_stamp = file_stamp(Path::new(&self.config.password_file_path));
+        self.stamped_password_file = self.config.password_file_path.clone();
+    }
+
+    fn has_local_edits(&self) -> bool {
+        self.passwords != self.saved_passwords
+    }
+
+    /// The password file as it is on disk now, `None` if it can't be read
+    /// without asking for the master password.
+    fn read_password_file(&self) -> Option<Dictionary> {
+        let password_file_pathbuf = PathBuf::from(&self.config.password_file_path);
+        match &self.vault.key {
+            Some(key) => {
+                let contents = key.reopen(&fs::read(&password_file_pathbuf).ok()?).ok()?;
+                Some(self.vault_dictionary(&contents))
+            }
+            None if is_vault(&password_file_pathbuf) => None,
+            None => Dictionary::load(&password_file_pathbuf).ok(),
+        }
+    }
+
+    /// Picks up changes someone else made to the password file. Without local
+    /// edits the file is simply reloaded, otherwise the user has to choose.
+    fn check_password_file(&mut self) {
+        let stamp = file_stamp(Path::new(&self.config.password_file_path));
+        if stamp == self.password_file_stamp
+            || self.stamped_password_file != self.config.password_file_path
+            || self.password_conflict.is_some()
+            || self.vault.locked
+        {
+            return;
+        }
+        self.password_file_stamp = stamp;
+        let on_disk = self.read_password_file();
+        if on_disk == self.saved_passwords {
+            // Touched, but the passwords and their metadata are the same.
+            return;
+        }
+        if !self.has_local_edits() {
+            debug!("Password file changed on disk, reloading");
+            self.update_passwords_from_file();
+        } else if let Some(on_disk) = on_disk {
+            debug!("Password file changed on disk and locally");
+            self.password_conflict = Some(on_disk);
         }
-        debug!("Passwords updated to {:?}", self.passwords);
+    }
+
+    fn password_conflict_ui(&mut self, ctx: &egui::Context) {
+        let Some(on_disk) = &self.password_conflict else {
+            return;
+        };
+        let rules = &self.config.sanitize_rules;
+        let local = self
+            .passwords
+            .as_ref()
+            .map(|passwords| passwords.passwords(rules))
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let theirs = on_disk.passwords(rules).into_iter().collect::<HashSet<_>>();
+        let mut choice = None;
+        egui::Window::new("Password file changed")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} was changed by another program while you were editing it.",
+                    self.config.password_file_path
+                ));
+                ui.label(format!(
+                    "{} password(s) are only here, {} only in the file.",
+                    local.difference(&theirs).count(),
+                    theirs.difference(&local).count()
+                ));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Merge")
+                        .on_hover_text("Keep everything from both versions")
+                        .clicked()
+                    {
+                        choice = Some(ConflictChoice::Merge);
+                    }
+                    if ui.button("Keep mine").clicked() {
+                        choice = Some(ConflictChoice::KeepMine);
+                    }
+                    if ui.button("Use the file").clicked() {
+                        choice = Some(ConflictChoice::UseTheirs);
+                    }
+                });
+            });
+        let Some(choice) = choice else {
+            return;
+        };
+        let on_disk = self.password_conflict.take().unwrap();
+        match choice {
+            ConflictChoice::UseTheirs => self.update_passwords_from_file(),
+            ConflictChoice::KeepMine | ConflictChoice::Merge => {
+                if choice == ConflictChoice::Merge {
+                    if let Some(passwords) = &mut self.passwords {
+                        passwords.merge_from(&on_disk);
+                    }
+                    self.try_sanitize_passwords();
+                }
+                // The file on disk has been looked at, so it may be overwritten.
+                self.password_file_stamp = file_stamp(Path::new(&self.config.password_file_path));
+                self.update_passwords_to_file();
+            }
+        }
+    }
+
+    fn usage_path(&self) -> PathBuf {
+        sidecar_path_for(Path::new(&self.config.password_file_path), ".stats.json")
+    }
+
+    fn archives_path(&self) -> PathBuf {
+        sidecar_path_for(Path::new(&self.config.password_file_path), ".archives.json")
+    }
+
+    /// Writes the usage statistics and the archive index next to a plaintext password file.
+    fn save_usage(&self) -> anyhow::Result<()> {
+        self.usage.save(&self.usage_path())?;
+        self.archives.save(&self.archives_path())
+    }
+
+    fn record_finished_tasks(&mut self) {
+        let finished = self.task_showcase.poll();
+        if finished.is_empty() || self.passwords.is_none() {
+            return;
+        }
+        for FinishedExtraction {
+            outcome,
+            fingerprint,
+            ..
+        } in finished
+        {
+            let Some(password) = &outcome.password else {
+                continue;
+            };
+            self.usage.record(password);
+            if let Some(fingerprint) = fingerprint {
+                self.archives.record(fingerprint.clone(), password);
+            }
+            let known = self
+                .passwords
+                .iter()
+                .chain(self.other_dictionaries.values())
+                .any(|dictionary| {
+                    dictionary
+                        .passwords(&self.config.sanitize_rules)
+                        .contains(password)
+                });
+            if !known && !self.harvested_suggestions.contains(password) {
+                self.harvested_suggestions.push(password.clone());
+            }
+        }
+        if self.vault.key.is_some() {
+            self.usage_unsaved_since.get_or_insert_with(Instant::now);
+        } else if let Err(e) = self.save_usage_changes() {
+            self.report_error("Could not save password usage", e);
+        }
+    }
+
+    /// Writes usage recorded in a vault once it has waited `VAULT_SAVE_DELAY`,
+    /// or right away when `now` is set.
+    fn save_pending_usage(&mut self, now: bool) {
+        let Some(since) = self.usage_unsaved_since else {
+            return;
+        };
+        if !now && since.elapsed() < VAULT_SAVE_DELAY {
+            return;
+        }
+        if let Err(e) = self.save_usage_changes() {
+            self.usage_unsaved_since = None;
+            self.report_error("Could not save password usage", e);
+        }
+    }
+
+    /// Saves the usage statistics and remembered archives wherever they are kept.
+    fn save_usage_changes(&mut self) -> anyhow::Result<()> {
+        if self.vault.key.is_some() {
+            // Sealing the usage rewrites the whole vault, which must not
+            // overwrite changes we have not seen yet.
+            self.check_password_file();
+            if self.password_conflict.is_some() {
+                debug!("Not saving usage until the conflict is resolved");
+                return Ok(());
+            }
+            self.write_password_file()?;
+            self.usage_unsaved_since = None;
+            // Only the stamp: local edits sealed along still get their own save and backup.
+            self.mark_password_file_stamp();
+            Ok(())
+        } else {
+            self.save_usage()
+        }
+    }
+
+    /// Probes in the background, a hanging executable must not freeze the window.
+    fn validate_archive_executable(&mut self) {
+        let executable = self.config.archive_executable_path.clone();
+        self.probed_executable = executable.clone();
+        self.seven_zip_probe = Some(Task::new(format!("Probe {}", executable), move || {
+            probe_seven_zip(&executable)
+        }));
     }
 
     fn update_passwords_to_file(&mut self) {
+        // Never overwrite changes we have not seen yet.
+        self.check_password_file();
+        if self.password_conflict.is_some() {
+            debug!("Not writing passwords until the conflict is resolved");
+            return;
+        }
         debug!("Writing passwords to {}", self.config.password_file_path);
-        // Let filesystem handle the file sync issue.
+        // Saves that only record usage are not worth a backup.
+        if self.has_local_edits() {
+            if let Err(e) = create_backup(Path::new(&self.config.password_file_path), BACKUP_COUNT)
+            {
+                self.report_error("Could not back up the password file", e);
+                return;
+            }
+        }
         // Start a new thread to do the job?
-        let password_file_pathbuf = PathBuf::from(&self.config.password_file_path);
-        if Path::is_file(&password_file_pathbuf) && self.passwords.is_some() {
-            fs::write(&password_file_pathbuf, self.passwords.as_ref().unwrap()).unwrap();
+        if let Err(e) = self.write_password_file() {
+            self.report_error("Could not save the password file", e);
+            return;
+        }
+        if self.vault.key.is_some() {
+            // The vault holds the usage, so it was written too.
+            self.usage_unsaved_since = None;
         }
+        self.mark_passwords_saved();
     }
 
-    fn try_sanitize_passwords(&mut self) {
-        debug!("Sanitizing passwords");
-        if self.config.sanitize {
+    fn report_error(&mut self, context: &str, e: anyhow::Error) {
+        debug!("{}: {}", context, e);
+        self.error_message = Some(format!("{}: {}", context, e));
+    }
+
+    fn error_message_ui(&mut self, ctx: &egui::Context) {
+        let Some(message) = &self.error_message else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::TopBottomPanel::top("error_panel").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.colored_label(Color32::RED, message);
+                dismissed = ui.button("Dismiss").clicked();
+            });
+        });
+        if dismissed {
+            self.error_message = None;
+        }
+    }
+
+    fn import_wizard_ui(&mut self, ctx: &egui::Context) {
+        let (Some(wizard), Some(passwords)) = (&mut self.import_wizard, &self.passwords) else {
+            return;
+        };
+        let mut open = true;
+        let imported = egui::Window::new("Import passwords")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                wizard.ui(ui, passwords, &self.config.sanitize_rules)
+            })
+            .and_then(|response| response.inner.flatten());
+        if let Some(imported) = imported {
+            debug!(
+                "Importing {} passwords from {}",
+                imported.passwords.len(),
+                imported.source
+            );
             if let Some(passwords) = &mut self.passwords {
-                let mut dict = passwords
-                    .split('\n')
-                    .map(|s| s.trim())
-                    .filter(|s| !s.is_empty())
-                    .collect::<Vec<_>>();
-                dict.sort();
-                dict.dedup();
-                *passwords = dict.join("\n");
-            }
-        }
-    }
-
-    fn schedule_files(&mut self, files: &Vec<DroppedFile>) {
-        let current_config = self.config.clone();
-        let password_dict = match &self.passwords {
-            None => {
-                debug!("No password file set, this should not happen but will try to extract with dummy passwords");
-                vec!["dummy".to_owned()]
-            }
-            Some(passwords) => {
-                debug!("Using password: {}", passwords);
-                passwords
-                    .split('\n')
-                    .map(|s| s.trim().to_owned())
-                    .collect::<Vec<_>>()
+                for password in imported.passwords {
+                    passwords.push(password, imported.source);
+                }
             }
+            self.import_wizard = None;
+            self.try_sanitize_passwords();
+            self.update_passwords_to_file();
+        } else if !open {
+            self.import_wizard = None;
+        }
+    }
+
+    /// Saves the plain-text password file as a structured one and switches to
+    /// it. The old file is left alone.
+    fn migrate_to_structured(&mut self) {
+        let Some(passwords) = &self.passwords else {
+            return;
         };
-        files.iter().for_each(|file| {
-            if let Some(path) = &file.path {
-                debug!("Extracting file {:?}", path);
-                let path = path.clone();
-                let current_config = current_config.clone();
-                let password_dict = password_dict.clone();
-                let task = Task::new(path.display().to_string(), move || {
-                    let wpass = WPassInstance::new(
-                        password_dict,
-                        current_config.archive_executable_path.clone().into(),
-                    );
-                    let output = current_config.calculate_output_path_for(&path);
-                    let extract_result = wpass.try_extract(&path, &output);
-                    match &extract_result {
-                        Ok(files) => {
-                            debug!("Extracted file {:?} to {:?}", files, output);
-                            if current_config.delete_after_extract {
-                                debug!("Deleting file {:?}", path);
-                                files.iter().try_for_each(fs::remove_file).unwrap();
-                            }
-                        }
-                        Err(e) => {
-                            debug!("Failed to extract file {:?}: {}", path, e);
-                        }
+        let old_path = PathBuf::from(&self.config.password_file_path);
+        let file_name = old_path.with_extension("json");
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name(file_name.file_name().unwrap_or_default().to_string_lossy())
+            .add_filter("JSON", &["json"])
+            .save_file()
+        else {
+            return;
+        };
+        let mut structured = passwords.clone();
+        structured.format = DictionaryFormat::Structured;
+        if let Err(e) = structured.save(&path) {
+            self.report_error("Could not write the structured dictionary", e);
+            return;
+        }
+        // The usage statistics and remembered archives follow the passwords.
+        fs::copy(self.usage_path(), sidecar_path_for(&path, ".stats.json")).ok();
+        fs::copy(
+            self.archives_path(),
+            sidecar_path_for(&path, ".archives.json"),
+        )
+        .ok();
+        let new_path = path.display().to_string();
+        for source in &mut self.config.dictionaries {
+            if source.path == self.config.password_file_path {
+                source.path = new_path.clone();
+            }
+        }
+        debug!("Migrated the password file to the structured format");
+        self.config.password_file_path = new_path;
+        self.update_passwords_from_file();
+    }
+
+    fn dictionary_format_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(passwords) = &self.passwords else {
+            ui.label(if self.vault.locked {
+                "Locked"
+            } else {
+                "No password file loaded"
+            });
+            return;
+        };
+        if self.vault.key.is_some() {
+            ui.label("Encrypted vault, metadata included");
+            return;
+        }
+        match passwords.format {
+            DictionaryFormat::Structured => {
+                ui.label("Structured JSON with tags, notes and sources");
+            }
+            DictionaryFormat::PlainText => {
+                ui.horizontal(|ui| {
+                    ui.label("Plain text, metadata in a separate file");
+                    if ui
+                        .button("Convert to structured…")
+                        .on_hover_text("Writes a JSON copy with all metadata and switches to it")
+                        .clicked()
+                    {
+                        self.migrate_to_structured();
                     }
-                    extract_result
                 });
-                self.task_showcase.display(task);
+            }
+        }
+    }
+
+    /// Sanitizes right away, or first shows what would change if the user wants to confirm.
+    fn try_sanitize_passwords(&mut self) {
+        if !self.config.sanitize || self.sanitize_preview.is_some() {
+            return;
+        }
+        let Some(passwords) = &self.passwords else {
+            return;
+        };
+        if self.sanitize_declined.as_ref() == Some(passwords) {
+            return;
+        }
+        let mut sanitized = passwords.clone();
+        sanitized.sanitize(&self.config.sanitize_rules);
+        if sanitized.to_text() == passwords.to_text() {
+            return;
+        }
+        if self.config.confirm_sanitize {
+            debug!("Previewing sanitized passwords");
+            self.sanitize_preview = Some(sanitized);
+        } else {
+            self.apply_sanitize();
+        }
+    }
+
+    fn apply_sanitize(&mut self) {
+        debug!("Sanitizing passwords");
+        if let Some(passwords) = &mut self.passwords {
+            // Edits made while the preview was open are sanitized too.
+            passwords.sanitize(&self.config.sanitize_rules);
+            self.password_editor.clear_selection();
+        }
+    }
+
+    fn sanitize_preview_ui(&mut self, ctx: &egui::Context) {
+        let (Some(sanitized), Some(passwords)) = (&self.sanitize_preview, &self.passwords) else {
+            return;
+        };
+        let lines = |dictionary: &Dictionary| {
+            dictionary
+                .entries
+                .iter()
+                .map(|entry| entry.password.clone())
+                .collect::<Vec<_>>()
+        };
+        let diff = diff_passwords(&lines(passwords), &lines(sanitized));
+        let mut apply = false;
+        let mut close = false;
+        egui::Window::new("Sanitize password file")
+            .collapsible(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                if diff.is_empty() {
+                    ui.label("Only the order of the lines changes.");
+                } else {
+                    ui.label("These lines change:");
+                }
+                ui.push_id("sanitize_diff", |ui| {
+                    ui.set_max_height(300.0);
+                    diff_ui(ui, &diff);
+                });
+                ui.checkbox(
+                    &mut self.config.confirm_sanitize,
+                    "Ask before sanitizing the password file",
+                );
+                ui.horizontal(|ui| {
+                    apply = ui.button("Apply").clicked();
+                    close = ui.button("Keep as is").clicked();
+                });
+            });
+        if apply {
+            self.sanitize_preview = None;
+            self.apply_sanitize();
+            self.update_passwords_to_file();
+        } else if close {
+            self.sanitize_preview = None;
+            self.sanitize_declined = self.passwords.clone();
+        }
+    }
+
+    fn sanitize_rules_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.config.sanitize, "Sanitize when saving");
+        ui.add_enabled_ui(self.config.sanitize, |ui| {
+            let rules = &mut self.config.sanitize_rules;
+            ui.checkbox(&mut rules.trim, "Trim surrounding whitespace");
+            ui.checkbox(&mut rules.sort, "Sort");
+            ui.horizontal(|ui| {
+                ui.label("Duplicates:");
+                egui::ComboBox::from_id_source("sanitize_dedup")
+                    .selected_text(dedup_name(rules.dedup))
+                    .show_ui(ui, |ui| {
+                        for dedup in [Dedup::Off, Dedup::CaseSensitive, Dedup::CaseInsensitive] {
+                            ui.selectable_value(&mut rules.dedup, dedup, dedup_name(dedup));
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Unicode normalization:");
+                egui::ComboBox::from_id_source("sanitize_normalization")
+                    .selected_text(normalization_name(rules.normalization))
+                    .show_ui(ui, |ui| {
+                        for normalization in
+                            [Normalization::Off, Normalization::Nfc, Normalization::Nfkc]
+                        {
+                            ui.selectable_value(
+                                &mut rules.normalization,
+                                normalization,
+                                normalization_name(normalization),
+                            );
+                        }
+                    });
+            });
+            ui.checkbox(&mut rules.comments, "Lines starting with # are comments");
+            ui.checkbox(&mut self.config.confirm_sanitize, "Preview changes first");
+            if ui.button("Sanitize now").clicked() {
+                self.sanitize_declined = None;
+                self.try_sanitize_passwords();
+                if self.sanitize_preview.is_none() {
+                    self.update_passwords_to_file();
+                }
             }
         });
     }
+
+    fn schedule_files(&mut self, files: &[DroppedFile], choose_dictionaries: bool) {
+        let paths = files
+            .iter()
+            .filter_map(|file| file.path.clone())
+            .collect::<Vec<_>>();
+        self.schedule_dropped_paths(paths, choose_dictionaries);
+    }
+
+    /// Archives handed to us on the command line, e.g. by "Open With". They
+    /// are treated like dropped files, so call this after `init`.
+    pub fn open_paths(&mut self, paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+        // The output directory is derived from the archive's parent.
+        let paths = absolute_paths(&paths);
+        debug!("Opening {:?}", paths);
+        self.schedule_dropped_paths(paths, self.config.ask_dictionaries_on_drop);
+    }
+
+    /// Opens the files later launches hand over and raises the window for them.
+    pub fn listen(&mut self, listener: Listener, ctx: &egui::Context) {
+        self.launches = Some(listener.spawn(ctx.clone()));
+    }
+
+    fn receive_launches(&mut self, ctx: &egui::Context) {
+        let Some(launches) = &self.launches else {
+            return;
+        };
+        let received = launches.try_iter().collect::<Vec<_>>();
+        if received.is_empty() {
+            return;
+        }
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        self.open_paths(received.into_iter().flatten().collect());
+    }
+
+    /// Extracts the files now, or keeps them until the configuration is complete.
+    fn accept_paths(&mut self, paths: Vec<PathBuf>) {
+        if self.ready_to_extract() {
+            let current_config = self.config.clone();
+            self.schedule_paths(paths, &current_config);
+        } else {
+            debug!("Not ready to extract, queueing {} file(s)", paths.len());
+            self.pending_files.extend(paths);
+        }
+    }
+
+    fn schedule_pending_files(&mut self, ctx: &egui::Context) {
+        if !self.pending_files.is_empty() && self.settled_to_extract(ctx) {
+            let paths = std::mem::take(&mut self.pending_files);
+            let current_config = self.config.clone();
+            self.schedule_paths(paths, &current_config);
+        }
+    }
+
+    fn extraction_jobs(
+        &self,
+        paths: Vec<PathBuf>,
+        current_config: &AppConfig,
+    ) -> anyhow::Result<Vec<ExtractionJob>> {
+        let dictionaries = current_config
+            .dictionaries
+            .iter()
+            .filter(|source| source.enabled)
+            .filter_map(|source| {
+                if source.path == current_config.password_file_path {
+                    self.passwords.as_ref()
+                } else {
+                    self.other_dictionaries.get(&source.path)
+                }
+            })
+            .collect::<Vec<_>>();
+        if dictionaries.is_empty() {
+            anyhow::bail!("None of the chosen dictionaries is enabled and readable");
+        }
+        let merged =
+            merge_dictionaries(dictionaries.iter().copied(), &current_config.sanitize_rules);
+        debug!("Using {} passwords", merged.len());
+        let password_dict = self.usage.order(merged, current_config.trial_order);
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let (mut config, tags) = current_config.route(&path);
+                // A plain-text copy next to the files would give away what the vault protects.
+                config.write_password_sidecar &= !self.vault.is_active();
+                let passwords = prefer_tagged(
+                    password_dict.clone(),
+                    &dictionaries,
+                    &tags,
+                    &config.sanitize_rules,
+                );
+                ExtractionJob {
+                    path,
+                    config,
+                    passwords,
+                    archives: self.archives.clone(),
+                }
+            })
+            .collect())
+    }
+
+    fn schedule_paths(&mut self, paths: Vec<PathBuf>, current_config: &AppConfig) {
+        let count = paths.len();
+        match self.extraction_jobs(paths, current_config) {
+            Ok(jobs) => {
+                for job in jobs {
+                    let task = Task::new(job.path.display().to_string(), move || job.run());
+                    self.task_showcase.display(task);
+                }
+            }
+            Err(e) => self.report_error(&format!("Not extracting {} file(s)", count), e),
+        }
+    }
+
+    fn poll_watch_folders(&mut self) {
+        for (path, folder_config) in self.watch_folders.poll(&self.config) {
+            self.schedule_paths(vec![path], &folder_config);
+        }
+    }
+
     fn ready_to_extract(&self) -> bool {
-        !self.config.archive_executable_path.is_empty() && self.passwords.is_some()
+        self.missing_settings().is_empty()
+    }
+
+    /// Like `ready_to_extract`, for extracting without the user asking right
+    /// now: nothing starts while a path is half typed, or before the 7z path
+    /// as it is now has been probed successfully.
+    fn settled_to_extract(&self, ctx: &egui::Context) -> bool {
+        let editing = ctx.memory(|memory| {
+            [
+                RequiredSetting::ArchiveExecutable,
+                RequiredSetting::UnrarExecutable,
+                RequiredSetting::BsdtarExecutable,
+            ]
+            .iter()
+            .any(|setting| memory.has_focus(setting.id()))
+        });
+        let seven_zip_checked = !self.config.used_backends().contains(&BackendKind::SevenZip)
+            || (self.probed_executable == self.config.archive_executable_path
+                && self
+                    .seven_zip_probe
+                    .as_ref()
+                    .and_then(Task::result)
+                    .is_some_and(|result| result.is_ok()));
+        !editing && seven_zip_checked && self.ready_to_extract()
+    }
+
+    fn missing_settings(&self) -> Vec<RequiredSetting> {
+        let mut missing = Vec::new();
+        if self.vault.locked {
+            missing.push(RequiredSetting::MasterPassword);
+        } else if self.passwords.is_none() && self.other_dictionaries.is_empty() {
+            missing.push(RequiredSetting::PasswordFile);
+        }
+        for kind in self.config.used_backends() {
+            if !self.config.executable_for(kind).is_empty() {
+                continue;
+            }
+            match kind {
+                BackendKind::SevenZip => missing.push(RequiredSetting::ArchiveExecutable),
+                BackendKind::Unrar => missing.push(RequiredSetting::UnrarExecutable),
+                BackendKind::Bsdtar => missing.push(RequiredSetting::BsdtarExecutable),
+                BackendKind::Zip => {}
+            }
+        }
+        missing
+    }
+
+    fn missing_settings_ui(&mut self, ui: &mut egui::Ui) {
+        let missing = self.missing_settings();
+        if missing.is_empty() {
+            // Everything is set, but the paths are still being edited or checked.
+            if !self.pending_files.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(Color32::YELLOW, "⚠");
+                    ui.label(format!(
+                        "{} file(s) are waiting until the executable paths are checked",
+                        self.pending_files.len()
+                    ));
+                });
+                ui.separator();
+            }
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.colored_label(Color32::YELLOW, "⚠");
+            if self.pending_files.is_empty() {
+                ui.label("Nothing can be extracted until you set the");
+            } else {
+                let pending = self
+                    .pending_files
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ui.label(format!(
+                    "{} file(s) are waiting until you set the",
+                    self.pending_files.len()
+                ))
+                .on_hover_text(pending);
+            }
+            for (index, setting) in missing.iter().enumerate() {
+                if index > 0 {
+                    ui.label("and the");
+                }
+                if ui.link(setting.description()).clicked() {
+                    // The unlock prompt is shown on top of every view.
+                    if *setting != RequiredSetting::MasterPassword {
+                        self.menu_state = MenuState::Setting;
+                    }
+                    self.focus_setting = Some(*setting);
+                }
+            }
+        });
+        ui.separator();
+    }
+
+    fn format_backends_ui(&mut self, ui: &mut egui::Ui) {
+        for extension in ARCHIVE_EXTENSIONS {
+            let mut kind = self
+                .config
+                .format_backends
+                .get(*extension)
+                .copied()
+                .unwrap_or(BackendKind::SevenZip);
+            ui.horizontal(|ui| {
+                ui.label(format!(".{}", extension));
+                egui::ComboBox::from_id_source(("format_backend", extension))
+                    .selected_text(kind.name())
+                    .show_ui(ui, |ui| {
+                        for option in BackendKind::ALL {
+                            ui.selectable_value(&mut kind, option, option.name());
+                        }
+                    });
+            });
+            if kind == BackendKind::SevenZip {
+                self.config.format_backends.remove(*extension);
+            } else {
+                self.config
+                    .format_backends
+                    .insert(extension.to_string(), kind);
+            }
+        }
+    }
+
+    fn harvested_suggestions_ui(&mut self, ui: &mut egui::Ui) {
+        let mut handled = None;
+        // The password file may have been closed since the password worked.
+        let has_passwords = self.passwords.is_some();
+        for (index, password) in self.harvested_suggestions.iter().enumerate() {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("A password found next to an archive worked:");
+                ui.monospace(password.expose());
+                if ui
+                    .add_enabled(has_passwords, egui::Button::new("Add to dictionary"))
+                    .on_disabled_hover_text("Open a password file to add it to")
+                    .clicked()
+                {
+                    handled = Some((index, true));
+                }
+                if ui.button("Dismiss").clicked() {
+                    handled = Some((index, false));
+                }
+            });
+        }
+        if let Some((index, add)) = handled {
+            let password = self.harvested_suggestions.remove(index);
+            if add {
+                if let Some(passwords) = &mut self.passwords {
+                    passwords.push(password, "Harvested");
+                }
+                self.try_sanitize_passwords();
+                self.update_passwords_to_file();
+            }
+        }
+        if !self.harvested_suggestions.is_empty() {
+            ui.separator();
+        }
+    }
+
+    fn harvest_rules_ui(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for (index, rule) in self.config.harvest_rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut rule.enabled, "");
+                egui::ComboBox::from_id_source(("harvest_source", index))
+                    .selected_text(rule.source.name())
+                    .show_ui(ui, |ui| {
+                        for source in HarvestSource::ALL {
+                            ui.selectable_value(&mut rule.source, source, source.name());
+                        }
+                    });
+                if let Err(e) = rule.regex() {
+                    ui.colored_label(Color32::RED, "✖").on_hover_text(e);
+                }
+                ui.add_sized(
+                    ui.available_size() - Vec2::new(60.0, 0.0),
+                    egui::TextEdit::singleline(&mut rule.pattern).code_editor(),
+                );
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.config.harvest_rules.remove(index);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add rule").clicked() {
+                self.config
+                    .harvest_rules
+                    .push(HarvestRule::new(HarvestSource::FileName, ""));
+            }
+            if ui.button("Restore defaults").clicked() {
+                self.config.harvest_rules = default_rules();
+            }
+        });
     }
 }
 
 impl eframe::App for WPassApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.save_pending_usage(true);
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
@@ -225,8 +1315,65 @@ impl eframe::App for WPassApp {
                 if ui.button("Passwords").clicked() {
                     self.menu_state = MenuState::Password;
                 }
+                if !self.config.watch_folders.is_empty() {
+                    ui.separator();
+                    ui.toggle_value(&mut self.config.watch_paused, "Pause watching");
+                }
+                if self.menu_state == MenuState::Password && self.passwords.is_some() {
+                    ui.separator();
+                    if ui.button("Import…").clicked() {
+                        self.import_wizard = Some(ImportWizard::new());
+                    }
+                    let mut show_backups = self.backup_view.is_some();
+                    if ui
+                        .toggle_value(&mut show_backups, "Revert to backup…")
+                        .changed()
+                    {
+                        self.backup_view = show_backups.then(|| BackupView {
+                            backups: list_backups(Path::new(&self.config.password_file_path)),
+                            ..Default::default()
+                        });
+                    }
+                }
             });
         });
+        self.error_message_ui(ctx);
+        self.new_dictionary_ui(ctx);
+        self.import_wizard_ui(ctx);
+        self.sanitize_preview_ui(ctx);
+        if self.vault.locked {
+            self.unlock_vault_ui(ctx);
+        }
+        if self.drop_selection.is_some() {
+            self.drop_selection_ui(ctx);
+        }
+        let now = Instant::now();
+        let checked_recently = self
+            .last_password_check
+            .filter(|last| now.duration_since(*last) < crate::watcher::POLL_INTERVAL);
+        if checked_recently.is_none() {
+            self.last_password_check = Some(now);
+            self.check_password_file();
+        }
+        // Keep polling even when nobody moves the mouse.
+        ctx.request_repaint_after(crate::watcher::POLL_INTERVAL);
+        self.password_conflict_ui(ctx);
+        self.record_finished_tasks();
+        self.save_pending_usage(false);
+        if let Some(probe) = &mut self.seven_zip_probe {
+            probe.poll();
+            if probe.result().is_none() {
+                ctx.request_repaint_after(PROBE_POLL_INTERVAL);
+            }
+        }
+        self.receive_launches(ctx);
+        self.schedule_pending_files(ctx);
+        if !self.config.watch_folders.is_empty()
+            && !self.config.watch_paused
+            && self.settled_to_extract(ctx)
+        {
+            self.poll_watch_folders();
+        }
         match self.menu_state {
             MenuState::Main => {
                 if !ctx.input(|i| i.raw.hovered_files.is_empty()) {
@@ -265,27 +1412,71 @@ impl eframe::App for WPassApp {
                     );
                 } else {
                     egui::CentralPanel::default().show(ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            // Picked files are handled like dropped ones, Shift included.
+                            let choose = self.config.ask_dictionaries_on_drop
+                                || ui.input(|i| i.modifiers.shift);
+                            if ui.button("Add files…").clicked() {
+                                if let Some(paths) = rfd::FileDialog::new()
+                                    .add_filter("Archives", ARCHIVE_EXTENSIONS)
+                                    .pick_files()
+                                {
+                                    self.schedule_dropped_paths(paths, choose);
+                                }
+                            }
+                            if ui.button("Add folder…").clicked() {
+                                if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                                    self.schedule_dropped_paths(archives_in(&dir), choose);
+                                }
+                            }
+                        });
+                        self.missing_settings_ui(ui);
+                        self.harvested_suggestions_ui(ui);
                         if self.task_showcase.length() == 0 {
                             ui.centered_and_justified(|ui| {
                                 ui.label("Drag & Drop a file here");
                             });
                         } else {
-                            self.task_showcase.poll();
-                            self.task_showcase.ui(ui);
+                            self.task_showcase.ui(ui, |ui, finished| {
+                                if let Some(password) = &finished.outcome.password {
+                                    if ui
+                                        .small_button("📋")
+                                        .on_hover_text("Copy password")
+                                        .clicked()
+                                    {
+                                        let password = password.expose().to_owned();
+                                        ui.output_mut(|output| output.copied_text = password);
+                                    }
+                                }
+                                if let Some(encoding) = finished.outcome.encoding {
+                                    ui.weak(encoding.name()).on_hover_text(
+                                        "The archive expects the password in this codepage",
+                                    );
+                                }
+                                if let Some(names) = &mut finished.names {
+                                    names.ui(ui);
+                                }
+                            });
                         }
                     });
                 }
                 ctx.input(|i| {
                     if !i.raw.dropped_files.is_empty() {
-                        if self.ready_to_extract() {
-                            self.schedule_files(&i.raw.dropped_files);
-                        }
+                        let choose = self.config.ask_dictionaries_on_drop || i.modifiers.shift;
+                        self.schedule_files(&i.raw.dropped_files, choose);
                     }
                 });
             }
             // Showing settings
             MenuState::Setting => {
                 egui::CentralPanel::default().show(ctx, |ui| {
+                    if let Some(setting) = self
+                        .focus_setting
+                        .filter(|setting| *setting != RequiredSetting::MasterPassword)
+                    {
+                        self.focus_setting = None;
+                        ui.memory_mut(|memory| memory.request_focus(setting.id()));
+                    }
                     // The central panel the region left after adding TopPanel's and SidePanel's
                     egui::Grid::new("my_grid")
                         .num_columns(2)
@@ -296,8 +1487,9 @@ impl eframe::App for WPassApp {
                             ui.label("Path to password file:");
                             ui.horizontal(|ui| {
                                 let response = ui.add_sized(
-                                    ui.available_size() - Vec2::new(60.0, 0.0),
-                                    egui::TextEdit::singleline(&mut self.config.password_file_path),
+                                    ui.available_size() - Vec2::new(110.0, 0.0),
+                                    egui::TextEdit::singleline(&mut self.config.password_file_path)
+                                        .id(RequiredSetting::PasswordFile.id()),
                                 );
                                 if response.lost_focus() {
                                     self.update_passwords_from_file();
@@ -308,24 +1500,82 @@ impl eframe::App for WPassApp {
                                     }
                                     self.update_passwords_from_file();
                                 }
+                                if ui.button("New…").clicked() {
+                                    self.new_dictionary = Some(NewDictionary::default());
+                                }
+                            });
+                            ui.end_row();
+                            ui.label("Password dictionaries:");
+                            ui.vertical(|ui| {
+                                self.dictionaries_ui(ui);
                             });
                             ui.end_row();
                             ui.label("Path to 7z executable:");
-                            ui.horizontal(|ui| {
-                                ui.add_sized(
-                                    ui.available_size() - Vec2::new(60.0, 0.0),
-                                    egui::TextEdit::singleline(
-                                        &mut self.config.archive_executable_path,
-                                    ),
-                                );
-                                if ui.button("Browse").clicked() {
-                                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                                        self.config.archive_executable_path =
-                                            path.display().to_string();
+                            if executable_path_ui(
+                                ui,
+                                &mut self.config.archive_executable_path,
+                                RequiredSetting::ArchiveExecutable.id(),
+                                Some(&self.seven_zip_probe),
+                            ) {
+                                self.validate_archive_executable();
+                            }
+                            ui.end_row();
+                            ui.label("Path to unrar executable:");
+                            executable_path_ui(
+                                ui,
+                                &mut self.config.unrar_executable_path,
+                                RequiredSetting::UnrarExecutable.id(),
+                                None,
+                            );
+                            ui.end_row();
+                            ui.label("Path to bsdtar executable:");
+                            executable_path_ui(
+                                ui,
+                                &mut self.config.bsdtar_executable_path,
+                                RequiredSetting::BsdtarExecutable.id(),
+                                None,
+                            );
+                            ui.end_row();
+                            ui.label("Backend per format:");
+                            ui.vertical(|ui| {
+                                self.format_backends_ui(ui);
+                            });
+                            ui.end_row();
+                            ui.label("ZIP password encodings:");
+                            ui.horizontal_wrapped(|ui| {
+                                for encoding in LegacyEncoding::ALL {
+                                    let mut enabled = self.config.zip_encodings.contains(&encoding);
+                                    if ui.checkbox(&mut enabled, encoding.name()).changed() {
+                                        self.config.zip_encodings.retain(|e| *e != encoding);
+                                        if enabled {
+                                            self.config.zip_encodings.push(encoding);
+                                        }
                                     }
                                 }
-                            });
+                            })
+                            .response
+                            .on_hover_text(
+                                "Also tried after the UTF-8 password, in the order they were \
+                                 checked. Only the built-in zip backend can do this.",
+                            );
                             ui.end_row();
+                            let zip_backend =
+                                self.config.backend_kind_for(Path::new("archive.zip"));
+                            if !self.config.zip_encodings.is_empty()
+                                && zip_backend != BackendKind::Zip
+                            {
+                                ui.label("");
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.colored_label(Color32::YELLOW, "⚠");
+                                    ui.label(format!(
+                                        ".zip files are extracted with {}, which only tries \
+                                         the UTF-8 password. Set them to Built-in zip under \
+                                         Backend per format to use these encodings.",
+                                        zip_backend.name()
+                                    ));
+                                });
+                                ui.end_row();
+                            }
                             ui.label("Extraction destination:");
                             ui.vertical(|ui| {
                                 ui.radio_value(
@@ -338,35 +1588,178 @@ impl eframe::App for WPassApp {
                                     ExtractionMode::NewDirectory,
                                     "Extract to a new directory",
                                 );
+                                let is_template = matches!(
+                                    self.config.extraction_mode,
+                                    ExtractionMode::Template(_)
+                                );
+                                if ui
+                                    .radio(is_template, "Extract to a path template")
+                                    .clicked()
+                                    && !is_template
+                                {
+                                    self.config.extraction_mode =
+                                        ExtractionMode::Template(DEFAULT_TEMPLATE.to_owned());
+                                }
+                                if let ExtractionMode::Template(template) =
+                                    &mut self.config.extraction_mode
+                                {
+                                    ui.text_edit_singleline(template)
+                                        .on_hover_text(TEMPLATE_HELP);
+                                }
                             });
                             ui.end_row();
                             ui.label("Delete archive file:");
                             ui.checkbox(&mut self.config.delete_after_extract, "");
                             ui.end_row();
+                            ui.label("Password sidecar:");
+                            ui.add_enabled(
+                                !self.vault.is_active(),
+                                egui::Checkbox::new(
+                                    &mut self.config.write_password_sidecar,
+                                    "Save the password next to the extracted files",
+                                ),
+                            )
+                            .on_disabled_hover_text(
+                                "Not written while the password file is encrypted, \
+                                 it would be stored in plain text",
+                            );
+                            ui.end_row();
+                            ui.label("Garbled file names:");
+                            ui.checkbox(
+                                &mut self.config.fix_garbled_names,
+                                "Re-decode automatically when the codepage can be told",
+                            );
+                            ui.end_row();
+                            ui.label("Remembered archives:");
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}", self.archives.len()));
+                                if ui
+                                    .add_enabled(
+                                        !self.archives.is_empty(),
+                                        egui::Button::new("Forget"),
+                                    )
+                                    .clicked()
+                                {
+                                    self.archives = ArchiveIndex::new();
+                                    let saved = match self.vault.key {
+                                        Some(_) => self.write_password_file(),
+                                        None => self.save_usage(),
+                                    };
+                                    if let Err(e) = saved {
+                                        self.report_error("Could not save password usage", e);
+                                    }
+                                }
+                            });
+                            ui.end_row();
+                            ui.label("Font:");
+                            ui.horizontal(|ui| {
+                                let edit = ui.add(
+                                    egui::TextEdit::singleline(&mut self.config.custom_font_path)
+                                        .hint_text("Default and system fonts"),
+                                );
+                                let mut changed = edit.lost_focus();
+                                if ui.button("Browse").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("Fonts", &["ttf", "otf", "ttc"])
+                                        .pick_file()
+                                    {
+                                        self.config.custom_font_path = path.display().to_string();
+                                        changed = true;
+                                    }
+                                }
+                                if ui
+                                    .add_enabled(
+                                        !self.config.custom_font_path.is_empty(),
+                                        egui::Button::new("Clear"),
+                                    )
+                                    .clicked()
+                                {
+                                    self.config.custom_font_path.clear();
+                                    changed = true;
+                                }
+                                if changed {
+                                    self.update_fonts(ui.ctx());
+                                }
+                            });
+                            ui.end_row();
+                            ui.label("Password vault:");
+                            ui.vertical(|ui| {
+                                self.vault_settings_ui(ui);
+                            });
+                            ui.end_row();
+                            ui.label("Dictionary format:");
+                            ui.vertical(|ui| {
+                                self.dictionary_format_ui(ui);
+                            });
+                            ui.end_row();
                             ui.label("Sanitize password file:");
-                            ui.checkbox(&mut self.config.sanitize, "");
+                            ui.vertical(|ui| {
+                                self.sanitize_rules_ui(ui);
+                            });
+                            ui.end_row();
+                            ui.label("Password trial order:");
+                            ui.vertical(|ui| {
+                                ui.radio_value(
+                                    &mut self.config.trial_order,
+                                    TrialOrder::FileOrder,
+                                    "File order",
+                                );
+                                ui.radio_value(
+                                    &mut self.config.trial_order,
+                                    TrialOrder::MostSuccessful,
+                                    "Most successful first",
+                                );
+                                ui.radio_value(
+                                    &mut self.config.trial_order,
+                                    TrialOrder::MostRecent,
+                                    "Most recently used first",
+                                );
+                            });
+                            ui.end_row();
+                            ui.label("Password harvesting:");
+                            ui.vertical(|ui| {
+                                self.harvest_rules_ui(ui);
+                            });
+                            ui.end_row();
+                            ui.label("Routing rules:");
+                            ui.vertical(|ui| {
+                                self.routing_editor.ui(ui, &mut self.config);
+                            });
+                            ui.end_row();
+                            ui.label("Watched folders:");
+                            ui.vertical(|ui| {
+                                self.watch_folders.ui(ui, &mut self.config.watch_folders);
+                            });
                             ui.end_row();
                         });
                 });
             }
             MenuState::Password => {
-                // Check if password is set
-                if self.passwords.is_some() {
-                    egui::CentralPanel::default().show(ctx, |ui| {
-                        let response = ui.add_sized(
-                            ui.available_size(),
-                            egui::TextEdit::multiline(self.passwords.as_mut().unwrap()),
-                        );
-                        if response.lost_focus() {
-                            self.try_sanitize_passwords();
-                            self.update_passwords_to_file();
-                        }
+                if self.backup_view.is_some() {
+                    egui::SidePanel::right("backups_panel").show(ctx, |ui| {
+                        self.backups_ui(ui);
                     });
+                }
+                // Check if password is set
+                if let Some(passwords) = &mut self.passwords {
+                    let committed = egui::CentralPanel::default()
+                        .show(ctx, |ui| {
+                            self.password_editor.ui(ui, passwords, &self.usage)
+                        })
+                        .inner;
+                    if committed {
+                        self.try_sanitize_passwords();
+                        self.update_passwords_to_file();
+                    }
                 } else {
                     egui::CentralPanel::default().show(ctx, |ui| {
                         // The central panel the region left after adding TopPanel's and SidePanel's
-                        ui.centered_and_justified(|ui| {
+                        ui.vertical_centered(|ui| {
+                            ui.add_space(ui.available_height() / 3.0);
                             ui.label("Invalid password file, check it in settings.");
+                            if ui.button("Create new dictionary…").clicked() {
+                                self.new_dictionary = Some(NewDictionary::default());
+                            }
                         });
                     });
                 }
@@ -381,6 +1774,146 @@ impl eframe::App for WPassApp {
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ConflictChoice {
+    Merge,
+    KeepMine,
+    UseTheirs,
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Picks an `ExtractionMode` override, `None` meaning the default one.
+fn extraction_mode_override_ui(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    extraction_mode: &mut Option<ExtractionMode>,
+) {
+    let template = ExtractionMode::Template(match extraction_mode {
+        Some(ExtractionMode::Template(template)) => template.clone(),
+        _ => DEFAULT_TEMPLATE.to_owned(),
+    });
+    egui::ComboBox::from_id_source(id)
+        .selected_text(match extraction_mode {
+            None => "Default destination",
+            Some(ExtractionMode::Local) => "Same directory",
+            Some(ExtractionMode::NewDirectory) => "New directory",
+            Some(ExtractionMode::Template(_)) => "Path template",
+        })
+        .show_ui(ui, |ui| {
+            ui.selectable_value(extraction_mode, None, "Default destination");
+            ui.selectable_value(
+                extraction_mode,
+                Some(ExtractionMode::Local),
+                "Same directory",
+            );
+            ui.selectable_value(
+                extraction_mode,
+                Some(ExtractionMode::NewDirectory),
+                "New directory",
+            );
+            ui.selectable_value(extraction_mode, Some(template), "Path template");
+        });
+    if let Some(ExtractionMode::Template(template)) = extraction_mode {
+        ui.text_edit_singleline(template)
+            .on_hover_text(TEMPLATE_HELP);
+    }
+}
+
+/// Picks a delete-after-extract override, `None` meaning the default.
+fn delete_override_ui(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    delete_after_extract: &mut Option<bool>,
+) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(match delete_after_extract {
+            None => "Default deletion",
+            Some(true) => "Delete archive",
+            Some(false) => "Keep archive",
+        })
+        .show_ui(ui, |ui| {
+            ui.selectable_value(delete_after_extract, None, "Default deletion");
+            ui.selectable_value(delete_after_extract, Some(true), "Delete archive");
+            ui.selectable_value(delete_after_extract, Some(false), "Keep archive");
+        });
+}
+
+fn diff_ui(ui: &mut egui::Ui, diff: &PasswordDiff) {
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            for password in &diff.added {
+                ui.colored_label(Color32::GREEN, format!("+ {}", password.expose()));
+            }
+            for password in &diff.removed {
+                ui.colored_label(Color32::RED, format!("- {}", password.expose()));
+            }
+        });
+}
+
+fn dedup_name(dedup: Dedup) -> &'static str {
+    match dedup {
+        Dedup::Off => "Keep",
+        Dedup::CaseSensitive => "Remove",
+        Dedup::CaseInsensitive => "Remove, ignoring case",
+    }
+}
+
+fn normalization_name(normalization: Normalization) -> &'static str {
+    match normalization {
+        Normalization::Off => "Off",
+        Normalization::Nfc => "NFC",
+        Normalization::Nfkc => "NFKC",
+    }
+}
+
+/// Returns true when the user is done editing the path, so it can be validated.
+fn executable_path_ui(
+    ui: &mut egui::Ui,
+    path: &mut String,
+    id: Id,
+    probe: Option<&Option<Task<SevenZipInfo>>>,
+) -> bool {
+    ui.horizontal(|ui| {
+        let reserved = if probe.is_some() { 80.0 } else { 60.0 };
+        let mut done = ui
+            .add_sized(
+                ui.available_size() - Vec2::new(reserved, 0.0),
+                egui::TextEdit::singleline(path).id(id),
+            )
+            .lost_focus();
+        if ui.button("Browse").clicked() {
+            if let Some(picked) = rfd::FileDialog::new().pick_file() {
+                *path = picked.display().to_string();
+                done = true;
+            }
+        }
+        match probe.map(|probe| probe.as_ref().map(Task::result)) {
+            Some(Some(Some(Ok(info)))) => {
+                ui.colored_label(Color32::GREEN, "✔").on_hover_text(format!(
+                    "7-Zip {}\nFormats: {}",
+                    info.version,
+                    info.formats.join(", ")
+                ));
+            }
+            Some(Some(Some(Err(e)))) => {
+                ui.colored_label(Color32::RED, "✖")
+                    .on_hover_text(e.to_string());
+            }
+            Some(Some(None)) => {
+                ui.weak("checking…");
+            }
+            Some(None) | None => {}
+        }
+        done
+    })
+    .inner
+}
+
 fn powered_by_egui_and_eframe_and_lint_to_github(ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
         ui.spacing_mut().item_spacing.x = 0.0;
@@ -0,0 +1,145 @@
+use super::{diff_ui, WPassApp};
+use crate::backup::{diff_passwords, list_backups, write_atomic, Backup};
+use crate::passwords::{Dictionary, DictionaryFormat};
+use crate::vault::{is_vault, VaultContents};
+use egui::Color32;
+use log::debug;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// The backups of the password file, shown next to the password list.
+#[derive(Debug, Default)]
+pub struct BackupView {
+    pub backups: Vec<Backup>,
+    pub selected: Option<usize>,
+    /// The selected backup, read with the current key and metadata.
+    pub restored: Option<Result<Dictionary, String>>,
+}
+
+impl WPassApp {
+    /// Seals the plain-text backups with the vault key, or opens the sealed
+    /// ones again, so they stay usable recovery points. Deleting them would
+    /// lose those, and plain ones would leak what the vault protects.
+    pub(super) fn convert_backups(&self, encrypt: bool) -> anyhow::Result<()> {
+        let Some(key) = &self.vault.key else {
+            return Ok(());
+        };
+        let password_file_path = Path::new(&self.config.password_file_path);
+        for backup in list_backups(password_file_path) {
+            if is_vault(&backup.path) == encrypt {
+                continue;
+            }
+            debug!("Converting backup {:?}", backup.path);
+            let passwords = self.read_backup(&backup)?;
+            let mut contents = match (encrypt, DictionaryFormat::for_path(password_file_path)) {
+                (true, _) => key.seal(&VaultContents {
+                    passwords: passwords.to_text(),
+                    metadata: passwords.metadata(),
+                    usage: Default::default(),
+                    archives: Default::default(),
+                })?,
+                (false, DictionaryFormat::Structured) => passwords.to_structured()?.into_bytes(),
+                (false, DictionaryFormat::PlainText) => {
+                    passwords.to_text().expose().as_bytes().to_vec()
+                }
+            };
+            let written = write_atomic(&backup.path, &contents);
+            contents.zeroize();
+            written?;
+        }
+        Ok(())
+    }
+
+    fn read_backup(&self, backup: &Backup) -> anyhow::Result<Dictionary> {
+        let bytes = fs::read(&backup.path)?;
+        if is_vault(&backup.path) {
+            let key = self
+                .vault
+                .key
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("This backup is encrypted"))?;
+            let contents = key.reopen(&bytes)?;
+            return Ok(Dictionary::from_text(
+                contents.passwords.expose(),
+                &contents.metadata,
+            ));
+        }
+        let metadata = self
+            .passwords
+            .as_ref()
+            .map(Dictionary::metadata)
+            .unwrap_or_default();
+        let format = DictionaryFormat::for_path(Path::new(&self.config.password_file_path));
+        Dictionary::parse(&String::from_utf8_lossy(&bytes), &metadata, format)
+    }
+
+    pub(super) fn backups_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(view) = &self.backup_view else {
+            return;
+        };
+        ui.heading("Backups");
+        if view.backups.is_empty() {
+            ui.label("No backups yet, one is taken before each save.");
+        }
+        let mut selected = None;
+        for (index, backup) in view.backups.iter().enumerate() {
+            let taken = backup.taken.format("%Y-%m-%d %H:%M:%S").to_string();
+            if ui
+                .selectable_label(view.selected == Some(index), taken)
+                .clicked()
+            {
+                selected = Some(index);
+            }
+        }
+        if let Some(index) = selected {
+            let restored = self
+                .read_backup(&view.backups[index])
+                .map_err(|e| e.to_string());
+            let view = self.backup_view.as_mut().unwrap();
+            view.selected = Some(index);
+            view.restored = Some(restored);
+        }
+        ui.separator();
+        let view = self.backup_view.as_ref().unwrap();
+        let mut revert = false;
+        match &view.restored {
+            None => {
+                ui.label("Select a backup to compare it with the current list.");
+            }
+            Some(Err(e)) => {
+                ui.colored_label(Color32::RED, e);
+            }
+            Some(Ok(restored)) => {
+                let rules = &self.config.sanitize_rules;
+                let current = self
+                    .passwords
+                    .as_ref()
+                    .map(|passwords| passwords.passwords(rules))
+                    .unwrap_or_default();
+                let diff = diff_passwords(&current, &restored.passwords(rules));
+                revert = ui.button("Revert to this backup").clicked();
+                if diff.is_empty() {
+                    ui.label("Same passwords as the current list.");
+                }
+                diff_ui(ui, &diff);
+            }
+        }
+        if revert {
+            let Some(Some(Ok(restored))) = self.backup_view.take().map(|view| view.restored) else {
+                return;
+            };
+            debug!(
+                "Reverting to a backup with {} entries",
+                restored.entries.len()
+            );
+            self.passwords = Some(restored);
+            self.password_editor.clear_selection();
+            self.update_passwords_to_file();
+            self.backup_view = Some(BackupView {
+                backups: list_backups(Path::new(&self.config.password_file_path)),
+                ..Default::default()
+            });
+        }
+    }
+}
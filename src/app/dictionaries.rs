@@ -0,0 +1,322 @@
+use super::WPassApp;
+use crate::passwords::{Dictionary, DictionaryFormat};
+use crate::vault::is_vault;
+use egui::{Align2, Vec2};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One of the password lists merged when an archive is scheduled.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct DictionarySource {
+    pub path: String,
+    pub enabled: bool,
+}
+
+/// Dropped files waiting for the user to pick the dictionaries to try.
+#[derive(Debug)]
+pub struct DropSelection {
+    pub paths: Vec<PathBuf>,
+    /// Parallel to `AppConfig::dictionaries`.
+    pub enabled: Vec<bool>,
+}
+
+/// Options of the "Create new dictionary" dialog.
+#[derive(Debug, Default)]
+pub struct NewDictionary {
+    /// A list to start from, one password per line.
+    pub seed: Option<PathBuf>,
+}
+
+impl WPassApp {
+    /// Reloads every enabled dictionary besides the password file.
+    pub(super) fn update_other_dictionaries(&mut self) {
+        let password_file_path = &self.config.password_file_path;
+        if !password_file_path.is_empty()
+            && !self
+                .config
+                .dictionaries
+                .iter()
+                .any(|source| &source.path == password_file_path)
+        {
+            self.config.dictionaries.insert(
+                0,
+                DictionarySource {
+                    path: password_file_path.clone(),
+                    enabled: true,
+                },
+            );
+        }
+        self.other_dictionaries = self
+            .config
+            .dictionaries
+            .iter()
+            .filter(|source| source.enabled && &source.path != password_file_path)
+            .filter_map(|source| {
+                let path = Path::new(&source.path);
+                if is_vault(path) {
+                    debug!("Skipping encrypted dictionary {:?}", path);
+                    return None;
+                }
+                match Dictionary::load(path) {
+                    Ok(dictionary) => Some((source.path.clone(), dictionary)),
+                    Err(e) => {
+                        debug!("Failed to load dictionary {:?}: {}", path, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        debug!(
+            "{} other dictionaries loaded",
+            self.other_dictionaries.len()
+        );
+    }
+
+    /// Writes a new password file where the user wants it and switches to it.
+    fn create_dictionary(&mut self, seed: Option<PathBuf>) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("passwords.txt")
+            .save_file()
+        else {
+            return;
+        };
+        let mut dictionary = Dictionary {
+            format: DictionaryFormat::for_path(&path),
+            ..Default::default()
+        };
+        if let Some(seed) = seed {
+            match fs::read_to_string(&seed) {
+                Ok(text) => {
+                    for line in text.lines() {
+                        dictionary.push(line.into(), "Import");
+                    }
+                }
+                Err(e) => {
+                    self.report_error("Could not read the list to import", e.into());
+                    return;
+                }
+            }
+            if self.config.sanitize {
+                dictionary.sanitize(&self.config.sanitize_rules);
+            }
+        }
+        if let Err(e) = dictionary.save(&path) {
+            self.report_error("Could not create the password file", e);
+            return;
+        }
+        debug!(
+            "Created a dictionary with {} entries",
+            dictionary.entries.len()
+        );
+        self.config.password_file_path = path.display().to_string();
+        self.update_passwords_from_file();
+    }
+
+    pub(super) fn new_dictionary_ui(&mut self, ctx: &egui::Context) {
+        let Some(new_dictionary) = &mut self.new_dictionary else {
+            return;
+        };
+        let mut close = false;
+        let mut create = false;
+        egui::Window::new("Create new dictionary")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Start with the passwords from:");
+                    match &new_dictionary.seed {
+                        Some(seed) => {
+                            ui.label(seed.display().to_string());
+                            if ui.button("Clear").clicked() {
+                                new_dictionary.seed = None;
+                            }
+                        }
+                        None => {
+                            ui.weak("nothing, start empty");
+                        }
+                    }
+                    if ui.button("Browse").clicked() {
+                        if let Some(seed) = rfd::FileDialog::new().pick_file() {
+                            new_dictionary.seed = Some(seed);
+                        }
+                    }
+                });
+                ui.weak("Name the file .json to keep tags and notes inside it.");
+                ui.horizontal(|ui| {
+                    create = ui.button("Choose location and create").clicked();
+                    close = ui.button("Cancel").clicked();
+                });
+            });
+        if create {
+            let seed = self.new_dictionary.take().and_then(|options| options.seed);
+            self.create_dictionary(seed);
+        } else if close {
+            self.new_dictionary = None;
+        }
+    }
+
+    pub(super) fn dictionaries_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let mut moved_up = None;
+        let mut removed = None;
+        let count = self.config.dictionaries.len();
+        for (index, source) in self.config.dictionaries.iter_mut().enumerate() {
+            let is_password_file = source.path == self.config.password_file_path;
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut source.enabled, "").changed();
+                if ui.add_enabled(index > 0, egui::Button::new("⏶")).clicked() {
+                    moved_up = Some(index);
+                }
+                if ui
+                    .add_enabled(index + 1 < count, egui::Button::new("⏷"))
+                    .clicked()
+                {
+                    moved_up = Some(index + 1);
+                }
+                if ui
+                    .add_enabled(!is_password_file, egui::Button::new("Remove"))
+                    .on_disabled_hover_text("Choose another password file first")
+                    .clicked()
+                {
+                    removed = Some(index);
+                }
+                if is_password_file {
+                    ui.strong(&source.path)
+                        .on_hover_text("Edited in the password view");
+                } else {
+                    ui.label(&source.path);
+                }
+            });
+        }
+        if let Some(index) = moved_up {
+            self.config.dictionaries.swap(index - 1, index);
+        }
+        if let Some(index) = removed {
+            self.config.dictionaries.remove(index);
+            changed = true;
+        }
+        if ui.button("Add dictionary").clicked() {
+            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                let path = path.display().to_string();
+                if !self
+                    .config
+                    .dictionaries
+                    .iter()
+                    .any(|source| source.path == path)
+                {
+                    self.config.dictionaries.push(DictionarySource {
+                        path,
+                        enabled: true,
+                    });
+                    changed = true;
+                }
+            }
+        }
+        ui.checkbox(
+            &mut self.config.ask_dictionaries_on_drop,
+            "Choose dictionaries on every drop",
+        )
+        .on_hover_text("Hold Shift while dropping to choose once");
+        if changed {
+            self.update_other_dictionaries();
+        }
+    }
+
+    pub(super) fn schedule_dropped_paths(
+        &mut self,
+        paths: Vec<PathBuf>,
+        choose_dictionaries: bool,
+    ) {
+        if let Some(selection) = &mut self.drop_selection {
+            // Files dropped while choosing join the ones already waiting.
+            for path in paths {
+                if !selection.paths.contains(&path) {
+                    selection.paths.push(path);
+                }
+            }
+        } else if choose_dictionaries && !self.config.dictionaries.is_empty() {
+            self.drop_selection = Some(DropSelection {
+                enabled: self
+                    .config
+                    .dictionaries
+                    .iter()
+                    .map(|source| source.enabled)
+                    .collect(),
+                paths,
+            });
+        } else {
+            self.accept_paths(paths);
+        }
+    }
+
+    pub(super) fn drop_selection_ui(&mut self, ctx: &egui::Context) {
+        let ready = self.ready_to_extract();
+        let mut close = false;
+        let mut extract = false;
+        if let Some(selection) = &mut self.drop_selection {
+            egui::Window::new("Choose dictionaries")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Passwords to try on {} file(s):",
+                        selection.paths.len()
+                    ));
+                    for (source, enabled) in self
+                        .config
+                        .dictionaries
+                        .iter()
+                        .zip(selection.enabled.iter_mut())
+                    {
+                        ui.checkbox(enabled, &source.path);
+                    }
+                    ui.horizontal(|ui| {
+                        let any = selection.enabled.iter().any(|enabled| *enabled);
+                        extract = ui
+                            .add_enabled(ready && any, egui::Button::new("Extract"))
+                            .on_disabled_hover_text(if any {
+                                "Complete the settings first"
+                            } else {
+                                "Check at least one dictionary"
+                            })
+                            .clicked();
+                        close = ui.button("Cancel").clicked();
+                    });
+                });
+        }
+        if extract {
+            let selection = self.drop_selection.take().unwrap();
+            let mut current_config = self.config.clone();
+            for (source, enabled) in current_config
+                .dictionaries
+                .iter_mut()
+                .zip(selection.enabled)
+            {
+                source.enabled = enabled;
+            }
+            // Dictionaries disabled in the settings are not loaded yet.
+            let unloaded = current_config
+                .dictionaries
+                .iter()
+                .filter(|source| {
+                    source.enabled
+                        && source.path != current_config.password_file_path
+                        && !self.other_dictionaries.contains_key(&source.path)
+                })
+                .filter(|source| !is_vault(Path::new(&source.path)))
+                .filter_map(|source| {
+                    let dictionary = Dictionary::load(Path::new(&source.path)).ok()?;
+                    Some((source.path.clone(), dictionary))
+                })
+                .collect::<Vec<_>>();
+            self.other_dictionaries.extend(unloaded);
+            self.schedule_paths(selection.paths, &current_config);
+        } else if close {
+            self.drop_selection = None;
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use super::AppConfig;
+use crate::backend::{extract_staged, ExtractOutcome};
+use crate::codepage::LegacyEncoding;
+use crate::fingerprint::{fingerprint, ArchiveIndex};
+use crate::harvest::harvest;
+use crate::mojibake::{detect, find_garbled, redecode, NAME_ENCODINGS};
+use crate::secret::SecretString;
+use egui::Color32;
+use log::debug;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One archive to extract, with everything needed to do it off the UI thread.
+pub struct ExtractionJob {
+    pub path: PathBuf,
+    /// Already routed for `path`.
+    pub config: AppConfig,
+    /// The dictionaries, in trial order.
+    pub passwords: Vec<SecretString>,
+    pub archives: ArchiveIndex,
+}
+
+impl ExtractionJob {
+    pub fn run(self) -> anyhow::Result<FinishedExtraction> {
+        let ExtractionJob {
+            path,
+            config,
+            passwords,
+            archives,
+        } = self;
+        debug!("Extracting file {:?}", path);
+        let backend = config.backend_for(&path);
+        let output = config.calculate_output_path_for(&path);
+        // Taken before extracting, "delete after extract" may remove the archive.
+        let fingerprint = fingerprint(&path)
+            .map_err(|e| debug!("Could not fingerprint {:?}: {}", path, e))
+            .ok();
+        let mut seen = HashSet::new();
+        let seven_zip = Path::new(&config.archive_executable_path);
+        let mut candidates = harvest(&config.harvest_rules, &path, seven_zip)
+            .into_iter()
+            .chain(passwords)
+            .filter(|password| seen.insert(password.clone()))
+            .collect::<Vec<_>>();
+        let remembered = fingerprint
+            .as_deref()
+            .and_then(|fingerprint| archives.find(fingerprint, &candidates))
+            .cloned();
+        if let Some(remembered) = remembered {
+            debug!("Trying the password that opened {:?} before", path);
+            candidates.sort_by_key(|candidate| *candidate != remembered);
+        }
+        let outcome =
+            extract_staged(backend.as_ref(), &candidates, &path, &output).map_err(|e| {
+                debug!("Failed to extract file {:?}: {}", path, e);
+                e
+            })?;
+        debug!("Extracted file {:?} to {:?}", outcome.archives, output);
+        if let (true, Some(password)) = (config.write_password_sidecar, &outcome.password) {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let sidecar = output.join(format!("{}.password.txt", stem));
+            if let Err(e) = fs::write(&sidecar, password.expose()) {
+                debug!("Could not write {:?}: {}", sidecar, e);
+            }
+        }
+        if config.delete_after_extract {
+            debug!("Deleting file {:?}", path);
+            outcome
+                .archives
+                .iter()
+                .try_for_each(fs::remove_file)
+                .map_err(|e| {
+                    anyhow::anyhow!("Extracted, but could not delete the archive: {}", e)
+                })?;
+        }
+        Ok(FinishedExtraction {
+            names: GarbledNames::find(&outcome, &output, config.fix_garbled_names),
+            outcome,
+            fingerprint,
+            output,
+        })
+    }
+}
+
+/// The result of an extraction task.
+#[derive(Debug)]
+pub struct FinishedExtraction {
+    pub outcome: ExtractOutcome,
+    /// `None` when the archive could not be read to fingerprint it.
+    pub fingerprint: Option<String>,
+    pub output: PathBuf,
+    /// `None` when every extracted name looked fine.
+    pub names: Option<GarbledNames>,
+}
+
+/// Extracted entries whose names looked garbled, kept so the task row can
+/// re-decode them with another codepage.
+#[derive(Debug)]
+pub struct GarbledNames {
+    output: PathBuf,
+    /// Relative to `output`, as they were extracted.
+    pub entries: Vec<PathBuf>,
+    /// The codepage `entries` were re-decoded with, `None` for the names as extracted.
+    pub encoding: Option<LegacyEncoding>,
+    /// Why the last renaming failed.
+    error: Option<String>,
+}
+
+impl GarbledNames {
+    /// `None` if nothing in `outcome` looks garbled. Renames right away when
+    /// `fix` is set and the codepage can be told.
+    fn find(outcome: &ExtractOutcome, output: &Path, fix: bool) -> Option<Self> {
+        let entries = find_garbled(&outcome.entries);
+        if entries.is_empty() {
+            return None;
+        }
+        debug!("{} extracted names look garbled", entries.len());
+        let mut names = Self {
+            output: output.to_path_buf(),
+            entries,
+            encoding: None,
+            error: None,
+        };
+        if let Some(encoding) = detect(&names.entries).filter(|_| fix) {
+            debug!("Re-decoding the names as {}", encoding.name());
+            names.redecode(Some(encoding));
+        }
+        Some(names)
+    }
+
+    fn redecode(&mut self, encoding: Option<LegacyEncoding>) {
+        match redecode(&self.output, &self.entries, self.encoding, encoding) {
+            Ok(_) => {
+                self.encoding = encoding;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut encoding = self.encoding;
+        egui::ComboBox::from_id_source(ui.make_persistent_id("garbled_names"))
+            .selected_text(match encoding {
+                Some(encoding) => format!("Names as {}", encoding.name()),
+                None => "Garbled names".to_owned(),
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut encoding, None, "As extracted");
+                for name_encoding in NAME_ENCODINGS {
+                    ui.selectable_value(
+                        &mut encoding,
+                        Some(name_encoding),
+                        format!("Re-decode as {}", name_encoding.name()),
+                    );
+                }
+            })
+            .response
+            .on_hover_text(format!(
+                "{} names were not stored as UTF-8 and are renamed in place",
+                self.entries.len()
+            ));
+        if encoding != self.encoding {
+            self.redecode(encoding);
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(Color32::RED, "✖").on_hover_text(e);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use super::{ExtractionMode, FinishedExtraction, WPassApp};
+use crate::backend::ExtractOutcome;
+use crate::passwords::Dictionary;
+use crate::secret::SecretString;
+use crate::settings;
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// Holds the master password when the command line needs to open a vault.
+pub const MASTER_PASSWORD_VARIABLE: &str = "WPASS_MASTER_PASSWORD";
+
+/// Use from the command line, without a window.
+impl WPassApp {
+    /// The state the window persisted, without reading any password file yet.
+    pub fn load_headless() -> anyhow::Result<Self> {
+        Ok(settings::load()?.unwrap_or_default())
+    }
+
+    /// Loads the password files. A vault is unlocked with the master password
+    /// in `MASTER_PASSWORD_VARIABLE`.
+    pub fn open_password_files(&mut self) -> anyhow::Result<()> {
+        self.init();
+        if self.vault.locked {
+            let master_password = env::var(MASTER_PASSWORD_VARIABLE).map_err(|_| {
+                anyhow::anyhow!(
+                    "The password vault is locked, set {} to its master password",
+                    MASTER_PASSWORD_VARIABLE
+                )
+            })?;
+            self.vault.master_password = master_password.into();
+            self.unlock_vault();
+            if let Some(e) = self.vault.error.take() {
+                anyhow::bail!("Could not unlock the password vault: {}", e);
+            }
+        }
+        self.take_error()
+    }
+
+    pub fn save_headless(&self) -> anyhow::Result<()> {
+        settings::save(self)
+    }
+
+    /// Errors the window would have shown.
+    fn take_error(&mut self) -> anyhow::Result<()> {
+        match self.error_message.take() {
+            Some(message) => Err(anyhow::anyhow!(message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Extracts `paths` one after the other, as if they were dropped on the
+    /// window. With `test`, they are extracted into a temporary directory that
+    /// is removed again, which only finds their password.
+    pub fn extract_headless(
+        &mut self,
+        paths: Vec<PathBuf>,
+        test: bool,
+    ) -> anyhow::Result<Vec<(PathBuf, anyhow::Result<FinishedExtraction>)>> {
+        let missing = self.missing_settings();
+        if !missing.is_empty() {
+            let missing = missing
+                .iter()
+                .map(|setting| setting.description())
+                .collect::<Vec<_>>();
+            anyhow::bail!("Set the {} first", missing.join(" and the "));
+        }
+        let current_config = self.config.clone();
+        let mut results = Vec::new();
+        for (index, mut job) in self
+            .extraction_jobs(paths, &current_config)?
+            .into_iter()
+            .enumerate()
+        {
+            let path = job.path.clone();
+            let scratch = test.then(|| {
+                env::temp_dir().join(format!("wpass-gui-test-{}-{}", std::process::id(), index))
+            });
+            if let Some(scratch) = &scratch {
+                job.config.extraction_mode =
+                    ExtractionMode::Template(scratch.display().to_string());
+                job.config.delete_after_extract = false;
+                job.config.write_password_sidecar = false;
+                job.config.fix_garbled_names = false;
+            }
+            let result = job.run();
+            if let Some(scratch) = scratch {
+                fs::remove_dir_all(scratch).ok();
+            }
+            if let Ok(FinishedExtraction {
+                outcome:
+                    ExtractOutcome {
+                        password: Some(password),
+                        ..
+                    },
+                fingerprint,
+                ..
+            }) = &result
+            {
+                self.usage.record(password);
+                if let Some(fingerprint) = fingerprint {
+                    self.archives.record(fingerprint.clone(), password);
+                }
+            }
+            results.push((path, result));
+        }
+        if results.iter().any(|(_, result)| result.is_ok()) {
+            self.save_usage_changes()?;
+        }
+        Ok(results)
+    }
+
+    pub fn password_file(&self) -> anyhow::Result<&Dictionary> {
+        self.passwords
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No password file is set"))
+    }
+
+    /// Adds the passwords the password file does not have yet and saves it,
+    /// returning how many were added.
+    pub fn add_passwords(
+        &mut self,
+        passwords: Vec<SecretString>,
+        source: &str,
+        tags: &[String],
+    ) -> anyhow::Result<usize> {
+        let rules = self.config.sanitize_rules.clone();
+        let dictionary = self
+            .passwords
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No password file is set"))?;
+        let added = dictionary.push_new(passwords, source, tags, &rules);
+        if added > 0 {
+            // Sanitizing that asks for confirmation first is left to the window.
+            self.try_sanitize_passwords();
+            self.sanitize_preview = None;
+            self.update_passwords_to_file();
+            self.take_error()?;
+        }
+        Ok(added)
+    }
+
+    /// The settings as JSON, the way `config get` shows them.
+    pub fn config_json(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(&self.config)?)
+    }
+
+    /// Replaces the setting at `key`, with dots between the levels, by
+    /// `value`, which must have the type of the setting.
+    pub fn set_config_json(&mut self, key: &str, value: serde_json::Value) -> anyhow::Result<()> {
+        let mut config = self.config_json()?;
+        let setting = config
+            .pointer_mut(&format!("/{}", key.replace('.', "/")))
+            .ok_or_else(|| anyhow::anyhow!("There is no setting {}", key))?;
+        *setting = value;
+        self.config = serde_json::from_value(config)
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {}", key, e))?;
+        Ok(())
+    }
+}
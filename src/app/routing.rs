@@ -0,0 +1,188 @@
+use super::{delete_override_ui, extraction_mode_override_ui, AppConfig, ExtractionMode};
+use crate::rules::{PathPattern, PatternKind};
+use egui::{Color32, Vec2};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Overrides for archives whose path matches `pattern`. The first enabled
+/// matching rule applies.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct RoutingRule {
+    pub pattern: PathPattern,
+    pub enabled: bool,
+    /// Comma-separated; entries with any of these tags are tried first.
+    pub tags: String,
+    /// Overrides `AppConfig::extraction_mode` when set.
+    pub extraction_mode: Option<ExtractionMode>,
+    /// Overrides `AppConfig::delete_after_extract` when set.
+    pub delete_after_extract: Option<bool>,
+}
+
+impl RoutingRule {
+    fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    fn apply_to(&self, config: &AppConfig, captures: &BTreeMap<String, String>) -> AppConfig {
+        let mut config = config.clone();
+        if let Some(extraction_mode) = &self.extraction_mode {
+            config.extraction_mode = extraction_mode.clone();
+        }
+        config.template_captures = captures.clone();
+        if let Some(delete_after_extract) = self.delete_after_extract {
+            config.delete_after_extract = delete_after_extract;
+        }
+        config
+    }
+}
+
+impl AppConfig {
+    /// The first enabled rule matching `path`, with the named groups of its pattern.
+    fn matching_rule(&self, path: &Path) -> Option<(usize, BTreeMap<String, String>)> {
+        self.routing_rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.enabled)
+            .find_map(|(index, rule)| match rule.pattern.matches(path) {
+                Ok(captures) => Some((index, captures?)),
+                Err(e) => {
+                    debug!("Skipping routing rule {}: {}", index + 1, e);
+                    None
+                }
+            })
+    }
+
+    /// The configuration for `path` once the routing rules are applied, and
+    /// the password tags to try first.
+    pub(super) fn route(&self, path: &Path) -> (AppConfig, Vec<String>) {
+        match self.matching_rule(path) {
+            Some((index, captures)) => {
+                let rule = &self.routing_rules[index];
+                debug!("Routing rule {} matches {:?}", index + 1, path);
+                (rule.apply_to(self, &captures), rule.tag_list())
+            }
+            None => (self.clone(), Vec::new()),
+        }
+    }
+}
+
+/// UI state of the routing rules editor. The rules are part of the config.
+#[derive(Debug, Default)]
+pub struct RoutingEditor {
+    /// File name typed into the rules tester.
+    sample: String,
+}
+
+impl RoutingEditor {
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &mut AppConfig) {
+        let mut moved_up = None;
+        let mut removed = None;
+        let count = config.routing_rules.len();
+        for (index, rule) in config.routing_rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut rule.enabled, format!("{}.", index + 1));
+                egui::ComboBox::from_id_source(("rule_pattern_kind", index))
+                    .selected_text(rule.pattern.kind.name())
+                    .width(60.0)
+                    .show_ui(ui, |ui| {
+                        for kind in [PatternKind::Glob, PatternKind::Regex] {
+                            ui.selectable_value(&mut rule.pattern.kind, kind, kind.name());
+                        }
+                    });
+                if let Some(e) = rule.pattern.error() {
+                    ui.colored_label(Color32::RED, "✖").on_hover_text(e);
+                }
+                ui.add_sized(
+                    ui.available_size() - Vec2::new(130.0, 0.0),
+                    egui::TextEdit::singleline(&mut rule.pattern.pattern)
+                        .hint_text("Matched against the whole path")
+                        .code_editor(),
+                );
+                if ui.add_enabled(index > 0, egui::Button::new("⏶")).clicked() {
+                    moved_up = Some(index);
+                }
+                if ui
+                    .add_enabled(index + 1 < count, egui::Button::new("⏷"))
+                    .clicked()
+                {
+                    moved_up = Some(index + 1);
+                }
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut rule.tags)
+                        .hint_text("Tags to try first")
+                        .desired_width(140.0),
+                );
+                extraction_mode_override_ui(
+                    ui,
+                    ("rule_extraction_mode", index),
+                    &mut rule.extraction_mode,
+                );
+                delete_override_ui(
+                    ui,
+                    ("rule_delete_after_extract", index),
+                    &mut rule.delete_after_extract,
+                );
+            });
+        }
+        if let Some(index) = moved_up {
+            config.routing_rules.swap(index - 1, index);
+        }
+        if let Some(index) = removed {
+            config.routing_rules.remove(index);
+        }
+        if ui.button("Add rule").clicked() {
+            config.routing_rules.push(RoutingRule {
+                pattern: PathPattern::new(PatternKind::Glob, String::new()),
+                enabled: true,
+                tags: String::new(),
+                extraction_mode: None,
+                delete_after_extract: None,
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label("Test with:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.sample)
+                    .hint_text("/downloads/uploader/archive.rar"),
+            );
+        });
+        if !self.sample.is_empty() {
+            let sample = Path::new(&self.sample);
+            let (routed, tags) = config.route(sample);
+            match config.matching_rule(sample) {
+                Some((index, _)) => {
+                    ui.label(format!(
+                        "Rule {} applies: extracts to {}, {} the archive{}",
+                        index + 1,
+                        routed.calculate_output_path_for(sample).display(),
+                        if routed.delete_after_extract {
+                            "deletes"
+                        } else {
+                            "keeps"
+                        },
+                        if tags.is_empty() {
+                            String::new()
+                        } else {
+                            format!(", tries {} first", tags.join(", "))
+                        }
+                    ));
+                }
+                None => {
+                    ui.label("No rule applies, the defaults are used.");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use super::{RequiredSetting, WPassApp};
+use crate::passwords::{sidecar_path_for, Dictionary, DictionaryFormat};
+use crate::secret::SecretString;
+use crate::vault::{KdfParams, VaultContents, VaultKey};
+use egui::{Align2, Color32, Vec2};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State of an encrypted password file.
+#[derive(Debug, Default)]
+pub struct VaultState {
+    /// Set while the vault is unlocked.
+    pub key: Option<VaultKey>,
+    /// The password file is a vault that has not been unlocked yet.
+    pub locked: bool,
+    pub master_password: SecretString,
+    pub confirmation: SecretString,
+    pub error: Option<String>,
+}
+
+impl VaultState {
+    /// Whether the password file is a vault, unlocked or not.
+    pub fn is_active(&self) -> bool {
+        self.key.is_some() || self.locked
+    }
+}
+
+impl WPassApp {
+    pub(super) fn vault_dictionary(&self, contents: &VaultContents) -> Dictionary {
+        let mut passwords = Dictionary::from_text(contents.passwords.expose(), &contents.metadata);
+        // Used again once the vault is decrypted.
+        passwords.format = DictionaryFormat::for_path(Path::new(&self.config.password_file_path));
+        passwords
+    }
+
+    pub(super) fn use_vault_contents(&mut self, contents: VaultContents) {
+        self.passwords = Some(self.vault_dictionary(&contents));
+        self.usage = contents.usage;
+        self.archives = contents.archives;
+        self.vault.locked = false;
+    }
+
+    pub(super) fn unlock_vault(&mut self) {
+        let password_file_pathbuf = PathBuf::from(&self.config.password_file_path);
+        let opened = fs::read(&password_file_pathbuf)
+            .map_err(anyhow::Error::from)
+            .and_then(|sealed| VaultKey::open(&self.vault.master_password, &sealed));
+        self.vault.master_password = SecretString::default();
+        match opened {
+            Ok((key, contents)) => {
+                debug!("Vault unlocked");
+                self.vault.key = Some(key);
+                self.vault.error = None;
+                self.use_vault_contents(contents);
+                self.password_editor.clear_selection();
+            }
+            Err(e) => self.vault.error = Some(e.to_string()),
+        }
+    }
+
+    /// Turns the plaintext password file into a vault, or the other way round.
+    fn convert_password_file(&mut self, encrypt: bool) {
+        let password_file_pathbuf = PathBuf::from(&self.config.password_file_path);
+        if encrypt {
+            match VaultKey::create(&self.vault.master_password, KdfParams::default()) {
+                Ok(key) => self.vault.key = Some(key),
+                Err(e) => {
+                    self.vault.error = Some(e.to_string());
+                    return;
+                }
+            }
+        } else {
+            // Opened with the key that is about to go away.
+            if let Err(e) = self.convert_backups(false) {
+                self.report_error("Could not decrypt all backups of the password file", e);
+            }
+            self.vault.key = None;
+        }
+        self.vault.master_password = SecretString::default();
+        self.vault.confirmation = SecretString::default();
+        self.vault.error = None;
+        self.update_passwords_to_file();
+        if encrypt {
+            // The sidecars would leak the passwords the vault is protecting.
+            for suffix in [".meta.json", ".stats.json", ".archives.json"] {
+                fs::remove_file(sidecar_path_for(&password_file_pathbuf, suffix)).ok();
+            }
+            if let Err(e) = self.convert_backups(true) {
+                self.report_error("Could not encrypt all backups of the password file", e);
+            }
+            self.backup_view = None;
+        } else if let Err(e) = self.save_usage() {
+            self.report_error("Could not save password usage", e);
+        }
+    }
+
+    pub(super) fn vault_settings_ui(&mut self, ui: &mut egui::Ui) {
+        if self.vault.locked {
+            ui.label("Locked");
+            return;
+        }
+        if self.passwords.is_none() {
+            ui.label("No password file loaded");
+            return;
+        }
+        if self.vault.key.is_some() {
+            if ui
+                .button("Decrypt to plaintext")
+                .on_hover_text("Stores the passwords unencrypted again")
+                .clicked()
+            {
+                self.convert_password_file(false);
+            }
+            return;
+        }
+        ui.add(
+            egui::TextEdit::singleline(self.vault.master_password.expose_mut())
+                .password(true)
+                .hint_text("New master password"),
+        );
+        ui.add(
+            egui::TextEdit::singleline(self.vault.confirmation.expose_mut())
+                .password(true)
+                .hint_text("Repeat master password"),
+        );
+        let matching = !self.vault.master_password.is_empty()
+            && self.vault.master_password == self.vault.confirmation;
+        if ui
+            .add_enabled(matching, egui::Button::new("Encrypt password file"))
+            .clicked()
+        {
+            self.convert_password_file(true);
+        }
+        if let Some(error) = &self.vault.error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    pub(super) fn unlock_vault_ui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Unlock password vault")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("{} is encrypted.", self.config.password_file_path));
+                let response = ui.add(
+                    egui::TextEdit::singleline(self.vault.master_password.expose_mut())
+                        .id(RequiredSetting::MasterPassword.id())
+                        .password(true)
+                        .hint_text("Master password"),
+                );
+                if self.focus_setting == Some(RequiredSetting::MasterPassword) {
+                    self.focus_setting = None;
+                    response.request_focus();
+                }
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Unlock").clicked() || submitted {
+                    self.unlock_vault();
+                }
+                if let Some(error) = &self.vault.error {
+                    ui.colored_label(Color32::RED, error);
+                }
+            });
+    }
+}
@@ -0,0 +1,96 @@
+use super::{delete_override_ui, extraction_mode_override_ui, AppConfig, ExtractionMode};
+use crate::watcher::FolderWatcher;
+use egui::Vec2;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// A directory whose new archives are extracted without being dropped on the window.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WatchFolder {
+    pub path: String,
+    /// Overrides `AppConfig::extraction_mode` when set.
+    pub extraction_mode: Option<ExtractionMode>,
+    /// Overrides `AppConfig::delete_after_extract` when set.
+    pub delete_after_extract: Option<bool>,
+}
+
+impl WatchFolder {
+    fn apply_to(&self, config: &AppConfig) -> AppConfig {
+        let mut config = config.clone();
+        if let Some(extraction_mode) = &self.extraction_mode {
+            config.extraction_mode = extraction_mode.clone();
+        }
+        if let Some(delete_after_extract) = self.delete_after_extract {
+            config.delete_after_extract = delete_after_extract;
+        }
+        config
+    }
+}
+
+/// Watches the configured folders. The folders themselves are part of the config.
+#[derive(Debug)]
+pub struct WatchFolders {
+    watcher: FolderWatcher,
+}
+
+impl WatchFolders {
+    pub fn new() -> Self {
+        Self {
+            watcher: FolderWatcher::new(),
+        }
+    }
+
+    /// The archives that are ready, each with the configuration of its folder.
+    pub fn poll(&mut self, config: &AppConfig) -> Vec<(PathBuf, AppConfig)> {
+        let dirs = config
+            .watch_folders
+            .iter()
+            .map(|folder| PathBuf::from(&folder.path))
+            .collect::<Vec<_>>();
+        self.watcher
+            .poll(&dirs, Instant::now())
+            .into_iter()
+            .map(|(index, path)| (path, config.watch_folders[index].apply_to(config)))
+            .collect()
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, folders: &mut Vec<WatchFolder>) {
+        let mut removed = None;
+        for (index, folder) in folders.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add_sized(
+                    ui.available_size() - Vec2::new(60.0, 0.0),
+                    egui::TextEdit::singleline(&mut folder.path),
+                );
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+            ui.horizontal(|ui| {
+                extraction_mode_override_ui(
+                    ui,
+                    ("watch_extraction_mode", index),
+                    &mut folder.extraction_mode,
+                );
+                delete_override_ui(
+                    ui,
+                    ("watch_delete_after_extract", index),
+                    &mut folder.delete_after_extract,
+                );
+            });
+        }
+        if let Some(index) = removed {
+            folders.remove(index);
+        }
+        if ui.button("Add folder").clicked() {
+            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                folders.push(WatchFolder {
+                    path: path.display().to_string(),
+                    extraction_mode: None,
+                    delete_after_extract: None,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extensions we treat as archives when picking files up on our own,
+/// i.e. anything that was not explicitly handed to us by the user.
+pub const ARCHIVE_EXTENSIONS: &[&str] = &[
+    "7z", "zip", "rar", "tar", "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "cab", "iso", "001",
+];
+
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            ARCHIVE_EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        })
+        .unwrap_or(false)
+        && !is_later_volume(path)
+}
+
+/// `name.part2.rar` and up. Extracting the first volume reads the others;
+/// `.002` and up of `.001` sets are not in `ARCHIVE_EXTENSIONS` to begin with.
+fn is_later_volume(path: &Path) -> bool {
+    let is_rar = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("rar"));
+    let volume = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .and_then(|stem| {
+            let (_, number) = stem.rsplit_once(".part")?;
+            number.parse::<u32>().ok()
+        });
+    is_rar && volume.is_some_and(|volume| volume > 1)
+}
+
+/// `paths` relative to the current directory, which another process does not share.
+pub fn absolute_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let current_dir = env::current_dir().unwrap_or_default();
+    paths.iter().map(|path| current_dir.join(path)).collect()
+}
+
+/// Every archive below `dir`, sorted, with multi-volume sets by their first volume.
+pub fn archives_in(dir: &Path) -> Vec<PathBuf> {
+    let mut archives = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Not following links to directories, which may loop back up.
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                dirs.push(path);
+            } else if is_archive(&path) {
+                archives.push(path);
+            }
+        }
+    }
+    archives.sort();
+    archives
+}
+
+#[cfg(test)]
+mod test {
+    use super::archives_in;
+
+    #[test]
+    fn should_find_archives_recursively() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("b.RAR"), b"").unwrap();
+        std::fs::write(dir.join("readme.txt"), b"").unwrap();
+        std::fs::write(dir.join("nested/a.7z"), b"").unwrap();
+        std::fs::write(dir.join("c.part01.rar"), b"").unwrap();
+        std::fs::write(dir.join("c.part02.rar"), b"").unwrap();
+        std::fs::write(dir.join("d.7z.001"), b"").unwrap();
+        std::fs::write(dir.join("d.7z.002"), b"").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir, dir.join("nested/loop")).unwrap();
+
+        assert_eq!(
+            archives_in(dir),
+            vec![
+                dir.join("b.RAR"),
+                dir.join("c.part01.rar"),
+                dir.join("d.7z.001"),
+                dir.join("nested/a.7z")
+            ]
+        );
+    }
+}
@@ -0,0 +1,691 @@
+use crate::codepage::{password_variants, LegacyEncoding};
+use crate::probe::resolve_executable;
+use crate::secret::SecretString;
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wpass::{WPass, WPassInstance};
+use zeroize::Zeroize;
+
+/// What a successful extraction produced.
+#[derive(Debug)]
+pub struct ExtractOutcome {
+    /// The archive files that were consumed, which are the files removed by
+    /// "delete after extract".
+    pub archives: Vec<PathBuf>,
+    /// The password that opened the archive, `None` if the archive is not
+    /// encrypted or the backend cannot tell which candidate opened it.
+    pub password: Option<SecretString>,
+    /// The codepage the password was re-encoded into, `None` for UTF-8.
+    pub encoding: Option<LegacyEncoding>,
+    /// What was extracted, relative to the output directory.
+    pub entries: Vec<PathBuf>,
+}
+
+/// Every candidate was tried and none opened the archive. What can be told
+/// apart from a wrong password up front, a missing executable or an archive
+/// that cannot be read, is reported as an error of its own.
+#[derive(Debug)]
+pub struct NoPasswordFound;
+
+impl fmt::Display for NoPasswordFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No password in the dictionary opens this archive")
+    }
+}
+
+impl std::error::Error for NoPasswordFound {}
+
+/// Something that can open an archive given a list of candidate passwords.
+pub trait ExtractionBackend {
+    fn try_extract(
+        &self,
+        passwords: &[SecretString],
+        archive: &Path,
+        output: &Path,
+    ) -> Result<ExtractOutcome>;
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum BackendKind {
+    SevenZip,
+    Unrar,
+    Bsdtar,
+    Zip,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 4] = [
+        BackendKind::SevenZip,
+        BackendKind::Unrar,
+        BackendKind::Bsdtar,
+        BackendKind::Zip,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendKind::SevenZip => "7z",
+            BackendKind::Unrar => "unrar",
+            BackendKind::Bsdtar => "bsdtar",
+            BackendKind::Zip => "Built-in zip",
+        }
+    }
+
+    /// `executable` is ignored by backends that do not spawn a process, and
+    /// `zip_encodings` by those that cannot pass raw password bytes.
+    pub fn create(
+        &self,
+        executable: PathBuf,
+        zip_encodings: Vec<LegacyEncoding>,
+    ) -> Box<dyn ExtractionBackend> {
+        match self {
+            BackendKind::SevenZip => Box::new(SevenZip { executable }),
+            BackendKind::Unrar => Box::new(Unrar { executable }),
+            BackendKind::Bsdtar => Box::new(Bsdtar { executable }),
+            BackendKind::Zip => Box::new(InProcessZip {
+                encodings: zip_encodings,
+            }),
+        }
+    }
+}
+
+/// Delegates to `wpass`, which drives the 7z executable.
+pub struct SevenZip {
+    executable: PathBuf,
+}
+
+/// How many candidates one wpass run tries. wpass does not say which one
+/// worked, so only the candidates of the run that succeeded are tested.
+const SEVEN_ZIP_BATCH: usize = 32;
+
+impl ExtractionBackend for SevenZip {
+    fn try_extract(
+        &self,
+        passwords: &[SecretString],
+        archive: &Path,
+        output: &Path,
+    ) -> Result<ExtractOutcome> {
+        // wpass reports a 7z that cannot run like a wrong password.
+        if self.executable.as_os_str().is_empty() {
+            return Err(anyhow!("The 7z path is not set"));
+        }
+        if resolve_executable(&self.executable).is_none() {
+            return Err(anyhow!(
+                "The 7z executable {:?} was not found",
+                self.executable
+            ));
+        }
+        let before = snapshot(output);
+        for batch in passwords.chunks(SEVEN_ZIP_BATCH) {
+            let wpass = WPassInstance::new(
+                batch
+                    .iter()
+                    .map(|password| password.expose().to_owned())
+                    .collect(),
+                self.executable.clone(),
+            );
+            match wpass.try_extract(&archive.to_path_buf(), &output.to_path_buf()) {
+                Ok(archives) => {
+                    return Ok(ExtractOutcome {
+                        archives,
+                        password: self.opened_with(batch, archive).cloned(),
+                        encoding: None,
+                        entries: new_entries(output, &before),
+                    })
+                }
+                Err(e) => {
+                    debug!("No password of a batch opened {:?}: {}", archive, e);
+                    remove_new_entries(output, &before);
+                }
+            }
+        }
+        Err(NoPasswordFound.into())
+    }
+}
+
+impl SevenZip {
+    /// The candidate of `batch` that opened `archive`. Any candidate opens an
+    /// archive that is not encrypted, so none is credited for it.
+    fn opened_with<'a>(
+        &self,
+        batch: &'a [SecretString],
+        archive: &Path,
+    ) -> Option<&'a SecretString> {
+        match self.is_encrypted(archive) {
+            Ok(false) => {
+                debug!("{:?} is not encrypted", archive);
+                return None;
+            }
+            Ok(true) => {}
+            Err(e) => debug!("Could not tell whether {:?} is encrypted: {}", archive, e),
+        }
+        self.identify(batch, archive)
+            .map_err(|e| debug!("The password of {:?} is unknown: {}", archive, e))
+            .ok()
+    }
+
+    /// Whether the listing shows an encrypted entry. Listing fails without a
+    /// password when the file names are encrypted too.
+    fn is_encrypted(&self, archive: &Path) -> Result<bool> {
+        Ok(match seven_zip_listing(&self.executable, archive)? {
+            Some(listing) => listing.lines().any(|line| line.trim() == "Encrypted = +"),
+            None => true,
+        })
+    }
+
+    /// The first candidate of `batch` that passes `7z t`, the order wpass tried them in.
+    fn identify<'a>(&self, batch: &'a [SecretString], archive: &Path) -> Result<&'a SecretString> {
+        if let [password] = batch {
+            return Ok(password);
+        }
+        let mut command = password_command(&self.executable, BackendKind::SevenZip)?;
+        command.arg("t").arg("--").arg(archive);
+        for password in batch {
+            if run_with_password(&mut command, password)? {
+                return Ok(password);
+            }
+        }
+        Err(anyhow!(
+            "{:?} was extracted, but 7z accepts none of the passwords tried",
+            archive
+        ))
+    }
+}
+
+/// The properties of `archive` and each of its entries, as `7z l -slt`
+/// prints them. `None` if 7z cannot list it without a password.
+pub fn seven_zip_listing(executable: &Path, archive: &Path) -> Result<Option<String>> {
+    let listing = password_command(executable, BackendKind::SevenZip)?
+        .arg("l")
+        .arg("-slt")
+        .arg("--")
+        .arg(archive)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    Ok(listing
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&listing.stdout).into_owned()))
+}
+
+pub struct Unrar {
+    executable: PathBuf,
+}
+
+impl ExtractionBackend for Unrar {
+    fn try_extract(
+        &self,
+        passwords: &[SecretString],
+        archive: &Path,
+        output: &Path,
+    ) -> Result<ExtractOutcome> {
+        let mut command = password_command(&self.executable, BackendKind::Unrar)?;
+        fs::create_dir_all(output)?;
+        command
+            .arg("x")
+            // Without a value unrar reads the password from stdin.
+            .arg("-p")
+            .arg("-y")
+            .arg("-o+")
+            .arg("--")
+            .arg(archive)
+            // unrar only treats the destination as a directory with a trailing separator.
+            .arg(format!("{}{}", output.display(), std::path::MAIN_SEPARATOR));
+        try_each_password(passwords, archive, output, command)
+    }
+}
+
+pub struct Bsdtar {
+    executable: PathBuf,
+}
+
+impl ExtractionBackend for Bsdtar {
+    fn try_extract(
+        &self,
+        passwords: &[SecretString],
+        archive: &Path,
+        output: &Path,
+    ) -> Result<ExtractOutcome> {
+        // Without `--passphrase` bsdtar asks for it, on stdin when there is no terminal.
+        let mut command = password_command(&self.executable, BackendKind::Bsdtar)?;
+        fs::create_dir_all(output)?;
+        command
+            .arg("-x")
+            .arg("-f")
+            .arg(archive)
+            .arg("-C")
+            .arg(output);
+        try_each_password(passwords, archive, output, command)
+    }
+}
+
+/// A command for a tool that asks for the password. It runs without a
+/// controlling terminal, so the tools read the password from stdin and it
+/// never shows up in the process list.
+fn password_command(executable: &Path, kind: BackendKind) -> Result<Command> {
+    if executable.as_os_str().is_empty() {
+        return Err(anyhow!("The {} path is not set", kind.name()));
+    }
+    let mut command = Command::new(executable);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe and touches no memory of ours.
+        unsafe {
+            command.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+    }
+    Ok(command)
+}
+
+/// Runs `command` with each password on its stdin until it exits successfully.
+fn try_each_password(
+    passwords: &[SecretString],
+    archive: &Path,
+    output: &Path,
+    mut command: Command,
+) -> Result<ExtractOutcome> {
+    let before = snapshot(output);
+    for password in passwords {
+        if run_with_password(&mut command, password)? {
+            return Ok(ExtractOutcome {
+                archives: vec![archive.to_path_buf()],
+                password: Some(password.clone()),
+                encoding: None,
+                entries: new_entries(output, &before),
+            });
+        }
+        remove_new_entries(output, &before);
+    }
+    Err(NoPasswordFound.into())
+}
+
+/// Runs `command` with `password` on its stdin; whether it exited successfully.
+fn run_with_password(command: &mut Command, password: &SecretString) -> Result<bool> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut line = format!("{}\n", password.expose());
+    // The tool may exit before reading it, e.g. when the archive is not encrypted.
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(line.as_bytes()).ok();
+    }
+    line.zeroize();
+    let status = child.wait()?;
+    if !status.success() {
+        debug!("{:?} exited with {}", command.get_program(), status);
+    }
+    Ok(status.success())
+}
+
+/// Extracts into a fresh directory inside `output` and then moves what came
+/// out into `output` itself. The staging directory holds exactly what this
+/// archive contained, however many tasks extract into `output` at once and
+/// whatever was there before; files already there are replaced, as the
+/// tools' overwrite switches would.
+pub fn extract_staged(
+    backend: &dyn ExtractionBackend,
+    passwords: &[SecretString],
+    archive: &Path,
+    output: &Path,
+) -> Result<ExtractOutcome> {
+    if passwords.is_empty() {
+        return Err(anyhow!("The password dictionary is empty"));
+    }
+    fs::File::open(archive).map_err(|e| anyhow!("Could not read {:?}: {}", archive, e))?;
+    let staging = Staging::create(output)?;
+    let mut outcome = backend.try_extract(passwords, archive, &staging.dir)?;
+    outcome.entries = {
+        let mut entries = snapshot(&staging.dir).into_iter().collect::<Vec<_>>();
+        entries.sort();
+        entries
+    };
+    if let Err(e) = move_into(&staging.dir, output) {
+        // Whatever could not be moved is kept rather than lost.
+        let kept = staging.keep();
+        return Err(anyhow!(
+            "Extracted into {:?}, but could not move everything out: {}",
+            kept,
+            e
+        ));
+    }
+    Ok(outcome)
+}
+
+/// Removed again when dropped, with whatever a failed extraction left in it.
+struct Staging {
+    dir: PathBuf,
+    keep: bool,
+}
+
+impl Staging {
+    fn create(output: &Path) -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        fs::create_dir_all(output)?;
+        loop {
+            let dir = output.join(format!(
+                ".wpass-staging-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(Self { dir, keep: false }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        self.dir.clone()
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            debug!("Could not remove {:?}: {}", self.dir, e);
+        }
+    }
+}
+
+/// Moves the contents of `from` into `to`, merging directories both have.
+fn move_into(from: &Path, to: &Path) -> io::Result<()> {
+    for child in fs::read_dir(from)? {
+        let child = child?;
+        let target = to.join(child.file_name());
+        let both_dirs = child.file_type()?.is_dir()
+            && fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.is_dir());
+        if both_dirs {
+            move_into(&child.path(), &target)?;
+        } else {
+            fs::rename(child.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Everything under `dir`, relative to it; empty if it does not exist yet.
+fn snapshot(dir: &Path) -> HashSet<PathBuf> {
+    let mut entries = HashSet::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let Ok(children) = fs::read_dir(dir.join(&relative)) else {
+            continue;
+        };
+        for child in children.flatten() {
+            let entry = relative.join(child.file_name());
+            if child.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                pending.push(entry.clone());
+            }
+            entries.insert(entry);
+        }
+    }
+    entries
+}
+
+/// Everything under `dir` that was not there `before`, relative to `dir` and
+/// parents first. External tools do not say what they extracted.
+fn new_entries(dir: &Path, before: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut entries = snapshot(dir)
+        .into_iter()
+        .filter(|entry| !before.contains(entry))
+        .collect::<Vec<_>>();
+    entries.sort();
+    entries
+}
+
+/// Removes what a failed attempt left in `dir`, so the next one starts clean.
+fn remove_new_entries(dir: &Path, before: &HashSet<PathBuf>) {
+    for entry in new_entries(dir, before).iter().rev() {
+        let path = dir.join(entry);
+        let removed = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(&path),
+            _ => fs::remove_file(&path),
+        };
+        if let Err(e) = removed {
+            debug!(
+                "Could not remove {:?} left by a failed attempt: {}",
+                path, e
+            );
+        }
+    }
+}
+
+/// Reads ZIP archives in process, so trying a password does not cost a process spawn.
+pub struct InProcessZip {
+    /// Tried for each password after its UTF-8 bytes.
+    encodings: Vec<LegacyEncoding>,
+}
+
+impl ExtractionBackend for InProcessZip {
+    fn try_extract(
+        &self,
+        passwords: &[SecretString],
+        archive: &Path,
+        output: &Path,
+    ) -> Result<ExtractOutcome> {
+        let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+        let entries = (0..zip.len())
+            .filter_map(|index| Some(zip.by_index_raw(index).ok()?.enclosed_name()?.to_path_buf()))
+            .collect::<Vec<_>>();
+        let encrypted = (0..zip.len())
+            .map(|index| {
+                matches!(
+                    zip.by_index(index),
+                    Err(zip::result::ZipError::UnsupportedArchive(message))
+                        if message == zip::result::ZipError::PASSWORD_REQUIRED
+                )
+            })
+            .collect::<Vec<_>>();
+        let before = snapshot(output);
+        for password in passwords {
+            for (encoding, mut bytes) in password_variants(password.expose(), &self.encodings) {
+                let extracted = extract_zip_with(&mut zip, &bytes, &encrypted, output);
+                bytes.zeroize();
+                match extracted {
+                    Ok(true) => {
+                        return Ok(ExtractOutcome {
+                            archives: vec![archive.to_path_buf()],
+                            password: encrypted.contains(&true).then(|| password.clone()),
+                            encoding,
+                            entries,
+                        })
+                    }
+                    Ok(false) => remove_new_entries(output, &before),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Err(NoPasswordFound.into())
+    }
+}
+
+/// Returns `Ok(false)` if the password turned out to be wrong. `encrypted`
+/// tells for each entry whether it is.
+fn extract_zip_with(
+    zip: &mut zip::ZipArchive<fs::File>,
+    password: &[u8],
+    encrypted: &[bool],
+    output: &Path,
+) -> Result<bool> {
+    for (index, &encrypted) in encrypted.iter().enumerate() {
+        let mut entry = match zip.by_index_decrypt(index, password)? {
+            Ok(entry) => entry,
+            Err(zip::result::InvalidPassword) => return Ok(false),
+        };
+        let name = match entry.enclosed_name() {
+            Some(name) => name.to_path_buf(),
+            None => {
+                debug!("Skipping zip entry with unsafe path {:?}", entry.name());
+                continue;
+            }
+        };
+        let target = output.join(name);
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create(&target)?;
+        if !copy_entry(&mut entry, &mut file, encrypted)
+            .map_err(|e| anyhow!("Could not extract {:?}: {}", target, e))?
+        {
+            debug!("Entry {:?} failed to decrypt", target);
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Copies an entry into `file`. Returns `Ok(false)` if reading an encrypted
+/// entry failed the way a wrong password makes it fail: ZipCrypto lets about
+/// one wrong password in 256 through the header check, and those produce data
+/// that does not decompress or fails the checksum. Anything else, like a full
+/// disk or a damaged entry that is not encrypted, is an error.
+fn copy_entry(entry: &mut impl io::Read, file: &mut fs::File, encrypted: bool) -> io::Result<bool> {
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = match entry.read(&mut buffer) {
+            Ok(0) => return Ok(true),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) if encrypted && is_decrypt_error(&e) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        file.write_all(&buffer[..read])?;
+    }
+}
+
+/// A checksum or authentication failure, or decrypted data that does not decompress.
+fn is_decrypt_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput
+    ) || e.to_string() == "Invalid checksum"
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_staged, remove_new_entries, snapshot, BackendKind, NoPasswordFound};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_extract_plain_zip_in_process() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let archive = dir.join("plain.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        writer
+            .start_file("inner/answer.txt", zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(b"42").unwrap();
+        writer.finish().unwrap();
+
+        let output = dir.join("out");
+        let backend = BackendKind::Zip.create(Default::default(), Vec::new());
+        let outcome = backend
+            .try_extract(&["whatever".into()], &archive, &output)
+            .unwrap();
+        assert_eq!(outcome.archives, vec![archive]);
+        // Any password opens a plain archive, so none is credited.
+        assert!(outcome.password.is_none());
+        assert_eq!(
+            std::fs::read_to_string(output.join("inner/answer.txt")).unwrap(),
+            "42"
+        );
+    }
+
+    #[test]
+    fn should_report_only_what_the_archive_held() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let archive = dir.join("plain.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        writer
+            .start_file("inner/answer.txt", zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(b"42").unwrap();
+        writer.finish().unwrap();
+        let output = dir.join("out");
+        std::fs::create_dir_all(output.join("inner")).unwrap();
+        std::fs::write(output.join("inner/old.txt"), b"old").unwrap();
+        std::fs::write(output.join("inner/answer.txt"), b"41").unwrap();
+
+        let backend = BackendKind::Zip.create(Default::default(), Vec::new());
+        let outcome =
+            extract_staged(backend.as_ref(), &["whatever".into()], &archive, &output).unwrap();
+        assert_eq!(
+            outcome.entries,
+            vec![PathBuf::from("inner"), PathBuf::from("inner/answer.txt")]
+        );
+        assert_eq!(
+            std::fs::read_to_string(output.join("inner/answer.txt")).unwrap(),
+            "42"
+        );
+        assert!(output.join("inner/old.txt").is_file());
+        // Nothing but the merged directory is left in the output.
+        assert_eq!(std::fs::read_dir(&output).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn should_remove_what_a_failed_attempt_left() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("kept")).unwrap();
+        std::fs::write(dir.join("kept/old.txt"), b"old").unwrap();
+        let before = snapshot(dir);
+        std::fs::write(dir.join("kept/partial.txt"), b"").unwrap();
+        std::fs::create_dir_all(dir.join("new/nested")).unwrap();
+        std::fs::write(dir.join("new/nested/partial.txt"), b"").unwrap();
+
+        remove_new_entries(dir, &before);
+        assert_eq!(snapshot(dir), before);
+    }
+
+    #[test]
+    fn should_not_take_damaged_plain_entry_for_wrong_password() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let archive = dir.join("damaged.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let stored =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("answer.txt", stored).unwrap();
+        writer.write_all(b"forty-two").unwrap();
+        writer.finish().unwrap();
+        let mut bytes = std::fs::read(&archive).unwrap();
+        let data = bytes
+            .windows(9)
+            .position(|window| window == b"forty-two")
+            .unwrap();
+        bytes[data] = b'F';
+        std::fs::write(&archive, bytes).unwrap();
+
+        let backend = BackendKind::Zip.create(Default::default(), Vec::new());
+        let error = backend
+            .try_extract(&["whatever".into()], &archive, &dir.join("out"))
+            .unwrap_err();
+        assert!(error.downcast_ref::<NoPasswordFound>().is_none());
+    }
+}
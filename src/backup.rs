@@ -0,0 +1,188 @@
+use crate::passwords::sidecar_path_for;
+use crate::secret::SecretString;
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// How many backups are kept per password file.
+pub const BACKUP_COUNT: usize = 10;
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// Writes to a temporary file first, so a crash never leaves `path` half written.
+/// A symlink stays in place and the file it points to is replaced, with the
+/// permissions it had.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let path = match fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        // Not there yet.
+        Err(_) => path.to_path_buf(),
+    };
+    let permissions = fs::metadata(&path)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let temp = sidecar_path_for(&path, ".tmp");
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        // Before writing, so the contents are never readable by more people.
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&temp, &path)) {
+        fs::remove_file(&temp).ok();
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Backups live in a directory next to the file, e.g. `passwords.txt.backups`.
+pub fn backup_dir_for(path: &Path) -> PathBuf {
+    sidecar_path_for(path, ".backups")
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken: DateTime<Local>,
+}
+
+/// Copies `path` into its backup directory and drops all but the `keep` newest backups.
+pub fn create_backup(path: &Path, keep: usize) -> Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let dir = backup_dir_for(path);
+    fs::create_dir_all(&dir)?;
+    fs::copy(
+        path,
+        dir.join(Local::now().format(TIMESTAMP_FORMAT).to_string()),
+    )?;
+    for old in list_backups(path).into_iter().skip(keep) {
+        fs::remove_file(old.path)?;
+    }
+    Ok(())
+}
+
+/// Newest first. Files we did not name ourselves are ignored.
+pub fn list_backups(path: &Path) -> Vec<Backup> {
+    let Ok(entries) = fs::read_dir(backup_dir_for(path)) else {
+        return Vec::new();
+    };
+    let mut backups = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let naive = NaiveDateTime::parse_from_str(name.to_str()?, TIMESTAMP_FORMAT).ok()?;
+            Some(Backup {
+                path: entry.path(),
+                taken: Local.from_local_datetime(&naive).earliest()?,
+            })
+        })
+        .collect::<Vec<_>>();
+    backups.sort_by_key(|backup| Reverse(backup.taken));
+    backups
+}
+
+/// What replacing one password list with another would change, each side in its own order.
+#[derive(Debug, Default)]
+pub struct PasswordDiff {
+    pub added: Vec<SecretString>,
+    pub removed: Vec<SecretString>,
+}
+
+impl PasswordDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Lines are counted, so a duplicate that goes away shows up as removed.
+pub fn diff_passwords(before: &[SecretString], after: &[SecretString]) -> PasswordDiff {
+    PasswordDiff {
+        added: lines_missing_from(after, before),
+        removed: lines_missing_from(before, after),
+    }
+}
+
+/// The lines of `lines` left over once each line of `other` has cancelled one equal line.
+fn lines_missing_from(lines: &[SecretString], other: &[SecretString]) -> Vec<SecretString> {
+    let mut counts = HashMap::<&SecretString, usize>::new();
+    for line in other {
+        *counts.entry(line).or_default() += 1;
+    }
+    lines
+        .iter()
+        .filter(|line| match counts.get_mut(line) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create_backup, diff_passwords, list_backups, write_atomic};
+    use crate::secret::SecretString;
+
+    #[test]
+    fn should_rotate_backups() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("passwords.txt");
+        for version in ["a", "b", "c"] {
+            write_atomic(&path, version.as_bytes()).unwrap();
+            create_backup(&path, 2).unwrap();
+            // Backups are named by the millisecond.
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let backups = list_backups(&path);
+        assert_eq!(backups.len(), 2);
+        assert_eq!(std::fs::read_to_string(&backups[0].path).unwrap(), "c");
+        assert_eq!(std::fs::read_to_string(&backups[1].path).unwrap(), "b");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_write_through_symlinks_keeping_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let target = temp.path().join("real.txt");
+        let link = temp.path().join("passwords.txt");
+        std::fs::write(&target, "a").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o600)).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"b").unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "b");
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn should_diff_both_sides() {
+        let passwords = |list: &[&str]| {
+            list.iter()
+                .map(|password| SecretString::from(*password))
+                .collect::<Vec<_>>()
+        };
+        let diff = diff_passwords(&passwords(&["a", "b", "c"]), &passwords(&["c", "d", "a"]));
+        assert_eq!(diff.added, passwords(&["d"]));
+        assert_eq!(diff.removed, passwords(&["b"]));
+
+        let diff = diff_passwords(&passwords(&["a", "b", "a", "a"]), &passwords(&["b", "a"]));
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed, passwords(&["a", "a"]));
+    }
+}
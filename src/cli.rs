@@ -0,0 +1,449 @@
+use crate::app::{FinishedExtraction, WPassApp};
+use crate::backend::NoPasswordFound;
+use crate::desktop::{desktop_entry, install_desktop_entry};
+use crate::import::{parse_keepass_xml, split_text, CsvTable};
+use crate::secret::SecretString;
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Settings, the password file or the vault could not be used.
+const EXIT_ERROR: u8 = 1;
+
+/// Extracts password protected archives by trying a dictionary of passwords.
+/// Without a command the window opens and extracts the files given; the
+/// commands use its settings and password file.
+#[derive(Parser, Debug)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Print JSON instead of plain text.
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Archives for the window to extract, e.g. from "Open With". They go to
+    /// the window that is already open, if there is one.
+    pub files: Vec<PathBuf>,
+    /// Open another window even if one is running.
+    #[arg(long)]
+    pub new_window: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Extract archives the way dropping them on the window does.
+    ///
+    /// Exits with 3 if no password opened an archive and 4 if one failed for
+    /// another reason.
+    Extract {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Find the password of archives without keeping what they contain.
+    Test {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Read or add to the password file.
+    #[command(subcommand)]
+    Passwords(PasswordsCommand),
+    /// Read or change the settings.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Print a desktop entry that opens archives with this executable.
+    DesktopEntry {
+        /// Write it to the applications directory instead.
+        #[arg(long)]
+        install: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PasswordsCommand {
+    /// Print the entries of the password file.
+    List,
+    /// Add passwords that are not in the password file yet.
+    ///
+    /// Without passwords, or with `-`, they are read from stdin one per line,
+    /// which keeps them out of the shell history and the process list.
+    Add {
+        passwords: Vec<String>,
+        /// Tag the new entries, e.g. with the site they belong to.
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// Add the passwords of a KeePass XML export, a CSV file or a text list.
+    Import {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = ImportFormat::Auto)]
+        format: ImportFormat,
+        /// The CSV column, by header or 1-based index. Guessed from the header if not set.
+        #[arg(long)]
+        column: Option<String>,
+        /// Splits a text list; one password per line if not set.
+        #[arg(long, default_value = "")]
+        delimiter: String,
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print a setting, or all of them.
+    Get {
+        /// E.g. `delete_after_extract` or `sanitize_rules.trim`.
+        key: Option<String>,
+    },
+    /// Change a setting. Values are JSON, anything else is taken as a string.
+    Set { key: String, value: String },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    /// By the file extension.
+    Auto,
+    Keepass,
+    Csv,
+    Text,
+}
+
+/// What happened to one archive. The worst one decides the exit code.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+enum ArchiveStatus {
+    Extracted,
+    Opened,
+    NoPassword,
+    Failed,
+}
+
+impl ArchiveStatus {
+    fn exit_code(&self) -> u8 {
+        match self {
+            ArchiveStatus::Extracted | ArchiveStatus::Opened => 0,
+            ArchiveStatus::NoPassword => 3,
+            ArchiveStatus::Failed => 4,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ArchiveReport {
+    archive: PathBuf,
+    status: ArchiveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<PathBuf>,
+    /// Only shown by `test`, finding it is its purpose.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<SecretString>,
+    /// The codepage the password was re-encoded into.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<&'static str>,
+    /// The codepage garbled file names were re-decoded with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    names_decoded_as: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ArchiveReport {
+    fn new(archive: PathBuf, result: Result<FinishedExtraction>, test: bool) -> Self {
+        match result {
+            Ok(finished) => Self {
+                archive,
+                status: match test {
+                    true => ArchiveStatus::Opened,
+                    false => ArchiveStatus::Extracted,
+                },
+                output: (!test).then_some(finished.output),
+                password: finished.outcome.password.filter(|_| test),
+                encoding: finished.outcome.encoding.map(|encoding| encoding.name()),
+                names_decoded_as: finished
+                    .names
+                    .and_then(|names| names.encoding)
+                    .map(|encoding| encoding.name()),
+                error: None,
+            },
+            Err(e) => Self {
+                archive,
+                status: match e.downcast_ref::<NoPasswordFound>() {
+                    Some(_) => ArchiveStatus::NoPassword,
+                    None => ArchiveStatus::Failed,
+                },
+                output: None,
+                password: None,
+                encoding: None,
+                names_decoded_as: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    fn print(&self) {
+        let archive = self.archive.display();
+        match (&self.status, &self.output, &self.password) {
+            (ArchiveStatus::Extracted, Some(output), _) => {
+                println!("{}: extracted to {}", archive, output.display())
+            }
+            (ArchiveStatus::Opened, _, Some(password)) => {
+                println!("{}: opens with {}", archive, password.expose())
+            }
+            (ArchiveStatus::Opened, _, None) => {
+                println!(
+                    "{}: opens, not encrypted or with an unknown password",
+                    archive
+                )
+            }
+            _ => println!("{}: {}", archive, self.error.as_deref().unwrap_or("failed")),
+        }
+        if let Some(encoding) = self.encoding {
+            println!("  the password is encoded as {}", encoding);
+        }
+        if let Some(encoding) = self.names_decoded_as {
+            println!("  file names were re-decoded as {}", encoding);
+        }
+    }
+}
+
+impl Cli {
+    /// Parses the command line of this process. A file named like a command,
+    /// e.g. an archive called `test`, is opened rather than taken for it.
+    pub fn parse_launch() -> Self {
+        let cwd = env::current_dir().unwrap_or_default();
+        Self::parse_from(escape_file_names(env::args_os().collect(), &cwd))
+    }
+
+    /// Runs the command and returns the exit code.
+    pub fn run(self) -> u8 {
+        let json = self.json;
+        let Some(command) = self.command else {
+            return 0;
+        };
+        match run_command(command, json) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("error: {:#}", e);
+                EXIT_ERROR
+            }
+        }
+    }
+}
+
+/// Puts `--` before the first argument that is not an option if it is both a
+/// command name and a file in `cwd`, so clap takes it and the rest as files.
+fn escape_file_names(mut args: Vec<OsString>, cwd: &Path) -> Vec<OsString> {
+    let first = args
+        .iter()
+        .enumerate()
+        .skip(1)
+        .take_while(|(_, arg)| *arg != "--")
+        .find(|(_, arg)| !arg.to_string_lossy().starts_with('-'))
+        .map(|(index, _)| index);
+    if let Some(index) = first {
+        let arg = &args[index];
+        let command = Cli::command();
+        let is_command = arg == "help"
+            || command
+                .get_subcommands()
+                .any(|subcommand| subcommand.get_name() == arg);
+        if is_command && cwd.join(arg).is_file() {
+            args.insert(index, "--".into());
+        }
+    }
+    args
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// The settings and the password files, for the commands that use the passwords.
+fn load_with_passwords() -> Result<WPassApp> {
+    let mut app = WPassApp::load_headless()?;
+    app.open_password_files()?;
+    Ok(app)
+}
+
+fn run_command(command: Command, json: bool) -> Result<u8> {
+    match command {
+        Command::Extract { files } => extract(&mut load_with_passwords()?, files, false, json),
+        Command::Test { files } => extract(&mut load_with_passwords()?, files, true, json),
+        Command::Passwords(PasswordsCommand::List) => {
+            let app = load_with_passwords()?;
+            let dictionary = app.password_file()?;
+            if json {
+                print_json(&dictionary.entries)?;
+            } else {
+                for entry in &dictionary.entries {
+                    println!("{}", entry.password.expose());
+                }
+            }
+            Ok(0)
+        }
+        Command::Passwords(PasswordsCommand::Add { passwords, tags }) => {
+            let passwords = match passwords.as_slice() {
+                [] => read_stdin_passwords()?,
+                [dash] if dash == "-" => read_stdin_passwords()?,
+                _ => passwords.into_iter().map(SecretString::from).collect(),
+            };
+            let added = load_with_passwords()?.add_passwords(passwords, "Command line", &tags)?;
+            print_added(added, json)
+        }
+        Command::Passwords(PasswordsCommand::Import {
+            file,
+            format,
+            column,
+            delimiter,
+            tags,
+        }) => {
+            let (passwords, source) = read_import(&file, format, column.as_deref(), &delimiter)?;
+            let added = load_with_passwords()?.add_passwords(passwords, source, &tags)?;
+            print_added(added, json)
+        }
+        Command::Config(ConfigCommand::Get { key }) => {
+            let config = WPassApp::load_headless()?.config_json()?;
+            let value = match &key {
+                Some(key) => config
+                    .pointer(&format!("/{}", key.replace('.', "/")))
+                    .ok_or_else(|| anyhow!("There is no setting {}", key))?,
+                None => &config,
+            };
+            match value {
+                serde_json::Value::String(text) if !json => println!("{}", text),
+                value => print_json(value)?,
+            }
+            Ok(0)
+        }
+        Command::Config(ConfigCommand::Set { key, value }) => {
+            let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            let mut app = WPassApp::load_headless()?;
+            app.set_config_json(&key, value)?;
+            app.save_headless()?;
+            Ok(0)
+        }
+        Command::DesktopEntry { install } => {
+            match install {
+                true => println!("Installed {}", install_desktop_entry()?.display()),
+                false => print!("{}", desktop_entry(&env::current_exe()?)),
+            }
+            Ok(0)
+        }
+    }
+}
+
+/// One password per line; blank lines are skipped.
+fn read_stdin_passwords() -> Result<Vec<SecretString>> {
+    // Zeroized when dropped, like the passwords taken from it.
+    let text = SecretString::from(std::io::read_to_string(std::io::stdin())?);
+    let passwords = text
+        .expose()
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(SecretString::from)
+        .collect::<Vec<_>>();
+    if passwords.is_empty() {
+        return Err(anyhow!("No passwords given, on the command line or stdin"));
+    }
+    Ok(passwords)
+}
+
+fn extract(app: &mut WPassApp, files: Vec<PathBuf>, test: bool, json: bool) -> Result<u8> {
+    let reports = app
+        .extract_headless(files, test)?
+        .into_iter()
+        .map(|(archive, result)| ArchiveReport::new(archive, result, test))
+        .collect::<Vec<_>>();
+    if json {
+        print_json(&reports)?;
+    } else {
+        reports.iter().for_each(ArchiveReport::print);
+    }
+    Ok(reports
+        .iter()
+        .map(|report| report.status)
+        .max()
+        .map_or(0, |status| status.exit_code()))
+}
+
+fn print_added(added: usize, json: bool) -> Result<u8> {
+    if json {
+        print_json(&serde_json::json!({ "added": added }))?;
+    } else {
+        println!("{} passwords added", added);
+    }
+    Ok(0)
+}
+
+/// The passwords in `file` and the source recorded for them, like the import dialog.
+fn read_import(
+    file: &Path,
+    format: ImportFormat,
+    column: Option<&str>,
+    delimiter: &str,
+) -> Result<(Vec<SecretString>, &'static str)> {
+    let text = fs::read_to_string(file)?;
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let format = match (format, extension.as_deref()) {
+        (ImportFormat::Auto, Some("xml")) => ImportFormat::Keepass,
+        (ImportFormat::Auto, Some("csv")) => ImportFormat::Csv,
+        (ImportFormat::Auto, _) => ImportFormat::Text,
+        (format, _) => format,
+    };
+    match format {
+        ImportFormat::Keepass => Ok((parse_keepass_xml(&text)?, "KeePass")),
+        ImportFormat::Csv => {
+            let table = CsvTable::parse(&text, true)?;
+            let index = match column {
+                None => table.password_column(),
+                Some(column) => match column.parse::<usize>() {
+                    Ok(number) if number > 0 => number - 1,
+                    _ => table
+                        .headers
+                        .iter()
+                        .position(|header| header == column)
+                        .ok_or_else(|| anyhow!("There is no column {}", column))?,
+                },
+            };
+            Ok((table.column(index), "CSV"))
+        }
+        ImportFormat::Auto | ImportFormat::Text => Ok((split_text(&text, delimiter), "Import")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape_file_names, Cli};
+    use clap::Parser;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_open_files_named_like_commands() {
+        let temp = tempfile::tempdir().unwrap();
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let parse = |line: &[&str]| {
+            Cli::try_parse_from(escape_file_names(args(line), temp.path())).unwrap()
+        };
+
+        let cli = parse(&["wpass-gui", "test", "a.rar"]);
+        assert!(cli.command.is_some());
+
+        std::fs::write(temp.path().join("test"), b"").unwrap();
+        let cli = parse(&["wpass-gui", "--new-window", "test", "a.rar"]);
+        assert!(cli.command.is_none());
+        assert!(cli.new_window);
+        assert_eq!(
+            cli.files,
+            vec![PathBuf::from("test"), PathBuf::from("a.rar")]
+        );
+    }
+}
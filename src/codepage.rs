@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+/// A system codepage some ZIP tools encrypt with instead of UTF-8, e.g. on
+/// Chinese or Japanese Windows.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum LegacyEncoding {
+    Gbk,
+    Big5,
+    ShiftJis,
+    EucKr,
+    Cp437,
+    Windows1252,
+}
+
+impl LegacyEncoding {
+    pub const ALL: [LegacyEncoding; 6] = [
+        LegacyEncoding::Gbk,
+        LegacyEncoding::Big5,
+        LegacyEncoding::ShiftJis,
+        LegacyEncoding::EucKr,
+        LegacyEncoding::Cp437,
+        LegacyEncoding::Windows1252,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LegacyEncoding::Gbk => "GBK",
+            LegacyEncoding::Big5 => "Big5",
+            LegacyEncoding::ShiftJis => "Shift-JIS",
+            LegacyEncoding::EucKr => "CP949",
+            LegacyEncoding::Cp437 => "CP437",
+            LegacyEncoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    /// CP437 is not a web encoding, so encoding_rs does not know it.
+    fn web_encoding(&self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            LegacyEncoding::Gbk => Some(encoding_rs::GBK),
+            LegacyEncoding::Big5 => Some(encoding_rs::BIG5),
+            LegacyEncoding::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            // encoding_rs' EUC-KR is the CP949 superset.
+            LegacyEncoding::EucKr => Some(encoding_rs::EUC_KR),
+            LegacyEncoding::Windows1252 => Some(encoding_rs::WINDOWS_1252),
+            LegacyEncoding::Cp437 => None,
+        }
+    }
+
+    /// `None` if `text` has characters the codepage cannot represent.
+    pub fn encode(&self, text: &str) -> Option<Vec<u8>> {
+        match self.web_encoding() {
+            Some(encoding) => {
+                let (bytes, _, unmappable) = encoding.encode(text);
+                (!unmappable).then(|| bytes.into_owned())
+            }
+            None => oem_cp::encode_string_checked(text, &oem_cp::code_table::ENCODING_TABLE_CP437),
+        }
+    }
+
+    /// `None` if `bytes` are not valid in the codepage.
+    pub fn decode(&self, bytes: &[u8]) -> Option<String> {
+        match self.web_encoding() {
+            Some(encoding) => encoding
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(|text| text.into_owned()),
+            None => Some(oem_cp::decode_string_complete_table(
+                bytes,
+                &oem_cp::code_table::DECODING_TABLE_CP437,
+            )),
+        }
+    }
+}
+
+/// The bytes to try for `password`: UTF-8 first, then each of `encodings`
+/// that gives different bytes. `None` stands for UTF-8.
+pub fn password_variants(
+    password: &str,
+    encodings: &[LegacyEncoding],
+) -> Vec<(Option<LegacyEncoding>, Vec<u8>)> {
+    let mut variants = vec![(None, password.as_bytes().to_vec())];
+    for encoding in encodings {
+        if let Some(bytes) = encoding.encode(password) {
+            if variants.iter().all(|(_, tried)| *tried != bytes) {
+                variants.push((Some(*encoding), bytes));
+            }
+        }
+    }
+    variants
+}
+
+#[cfg(test)]
+mod test {
+    use super::{password_variants, LegacyEncoding};
+
+    #[test]
+    fn should_encode_in_legacy_codepages() {
+        let all = LegacyEncoding::ALL;
+        // ASCII is the same everywhere, so there is nothing more to try.
+        assert_eq!(password_variants("secret", &all).len(), 1);
+
+        let variants = password_variants("密码", &all);
+        assert_eq!(
+            variants[1],
+            (Some(LegacyEncoding::Gbk), vec![0xC3, 0xDC, 0xC2, 0xEB])
+        );
+        assert!(variants
+            .iter()
+            .all(|(encoding, _)| *encoding != Some(LegacyEncoding::Cp437)));
+
+        assert_eq!(
+            LegacyEncoding::ShiftJis.encode("パス"),
+            Some(vec![0x83, 0x70, 0x83, 0x58])
+        );
+        assert_eq!(LegacyEncoding::Cp437.encode("ü"), Some(vec![0x81]));
+    }
+}
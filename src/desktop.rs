@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The archive types the entry offers to open, see `ARCHIVE_EXTENSIONS`.
+pub const ARCHIVE_MIME_TYPES: &[&str] = &[
+    "application/x-7z-compressed",
+    "application/zip",
+    "application/vnd.rar",
+    "application/x-rar",
+    "application/x-rar-compressed",
+    "application/x-tar",
+    "application/gzip",
+    "application/x-compressed-tar",
+    "application/x-bzip2",
+    "application/x-bzip2-compressed-tar",
+    "application/x-xz",
+    "application/x-xz-compressed-tar",
+    "application/zstd",
+    "application/vnd.ms-cab-compressed",
+    "application/x-cd-image",
+];
+
+const DESKTOP_FILE_NAME: &str = "wpass-gui.desktop";
+
+/// Quotes the executable for the `Exec` key: the desktop entry spec escapes
+/// `"`, `` ` ``, `$` and `\` inside quotes, then `\` once more for the string value.
+fn quote_exec(executable: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in executable.display().to_string().chars() {
+        match c {
+            '"' | '`' | '$' => quoted.push_str(&format!("\\{}", c)),
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A desktop entry that lets file managers open archives with `executable`.
+pub fn desktop_entry(executable: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name={}\n\
+         Comment=Extract password protected archives\n\
+         Exec={} %F\n\
+         Terminal=false\n\
+         Categories=Utility;Archiving;Compression;\n\
+         MimeType={};\n",
+        crate::APP_NAME,
+        quote_exec(executable),
+        ARCHIVE_MIME_TYPES.join(";")
+    )
+}
+
+/// Writes the entry for the running executable to the user's applications
+/// directory and returns where it went.
+pub fn install_desktop_entry() -> Result<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or_else(|| anyhow!("Neither XDG_DATA_HOME nor HOME is set"))?;
+    let dir = data_home.join("applications");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(DESKTOP_FILE_NAME);
+    fs::write(&path, desktop_entry(&env::current_exe()?))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::desktop_entry;
+    use std::path::Path;
+
+    #[test]
+    fn should_quote_exec_line() {
+        let entry = desktop_entry(Path::new("/opt/my apps/$wpass-gui"));
+        assert!(entry.contains("Exec=\"/opt/my apps/\\$wpass-gui\" %F\n"));
+        assert!(entry.contains("application/vnd.rar;application/x-rar;"));
+        assert!(entry.ends_with(";\n"));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::backup::write_atomic;
+use crate::secret::{Salt, SecretString};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// How much of each end of an archive is hashed.
+const SAMPLE_LEN: u64 = 1024 * 1024;
+
+/// Identifies an archive by its size and a hash of its first and last MiB,
+/// so a renamed or re-downloaded copy is still recognised without reading
+/// all of it.
+pub fn fingerprint(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    let mut sample = Vec::new();
+    (&mut file).take(SAMPLE_LEN).read_to_end(&mut sample)?;
+    hasher.update(&sample);
+    if size > SAMPLE_LEN {
+        sample.clear();
+        file.seek(SeekFrom::Start((size - SAMPLE_LEN).max(SAMPLE_LEN)))?;
+        file.read_to_end(&mut sample)?;
+        hasher.update(&sample);
+    }
+    Ok(format!("{}-{:x}", size, hasher.finalize()))
+}
+
+/// The password that opened each archive we have seen, kept in a sidecar
+/// file next to the password file. Only a salted hash of the password is
+/// kept, so it is found again among the candidates.
+#[derive(Default, Serialize, Deserialize, Clone)]
+#[serde(from = "StoredIndex")]
+pub struct ArchiveIndex {
+    salt: Salt,
+    /// Password hashes by fingerprint.
+    passwords: HashMap<String, String>,
+}
+
+/// Indexes written before passwords were hashed hold the passwords themselves.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredIndex {
+    Hashed {
+        salt: Salt,
+        passwords: HashMap<String, String>,
+    },
+    Plain(HashMap<String, SecretString>),
+}
+
+impl From<StoredIndex> for ArchiveIndex {
+    fn from(stored: StoredIndex) -> Self {
+        match stored {
+            StoredIndex::Hashed { salt, passwords } => Self { salt, passwords },
+            StoredIndex::Plain(passwords) => {
+                let mut index = Self::new();
+                for (fingerprint, password) in passwords {
+                    index.record(fingerprint, &password);
+                }
+                index
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ArchiveIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ArchiveIndex({} archives)", self.passwords.len())
+    }
+}
+
+impl ArchiveIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A missing or unreadable index simply starts out empty.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.passwords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passwords.is_empty()
+    }
+
+    /// The candidate that opened the archive with `fingerprint` before.
+    pub fn find<'a>(
+        &self,
+        fingerprint: &str,
+        candidates: &'a [SecretString],
+    ) -> Option<&'a SecretString> {
+        let hash = self.passwords.get(fingerprint)?;
+        candidates
+            .iter()
+            .find(|candidate| self.salt.hash(candidate) == *hash)
+    }
+
+    pub fn record(&mut self, fingerprint: String, password: &SecretString) {
+        let hash = self.salt.hash(password);
+        self.passwords.insert(fingerprint, hash);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fingerprint, ArchiveIndex, SAMPLE_LEN};
+
+    #[test]
+    fn should_fingerprint_both_ends() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut contents = vec![0u8; 3 * SAMPLE_LEN as usize];
+        let original = dir.join("original.7z");
+        std::fs::write(&original, &contents).unwrap();
+        let copy = dir.join("renamed copy.7z");
+        std::fs::write(&copy, &contents).unwrap();
+        // Only the middle, which is not sampled, differs.
+        contents[SAMPLE_LEN as usize + 1] = 1;
+        let middle = dir.join("middle.7z");
+        std::fs::write(&middle, &contents).unwrap();
+        *contents.last_mut().unwrap() = 1;
+        let tail = dir.join("tail.7z");
+        std::fs::write(&tail, &contents).unwrap();
+
+        let original = fingerprint(&original).unwrap();
+        assert_eq!(fingerprint(&copy).unwrap(), original);
+        assert_eq!(fingerprint(&middle).unwrap(), original);
+        assert_ne!(fingerprint(&tail).unwrap(), original);
+
+        let mut index = ArchiveIndex::new();
+        index.record(original.clone(), &"secret".into());
+        let path = dir.join("index.json");
+        index.save(&path).unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+        let candidates = vec!["other".into(), "secret".into()];
+        assert_eq!(
+            ArchiveIndex::load(&path).find(&original, &candidates),
+            Some(&candidates[1])
+        );
+    }
+}
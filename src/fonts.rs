@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Fontconfig configuration whose `<dir>` entries are searched.
+const FONTCONFIG_FILES: &[&str] = &["/etc/fonts/fonts.conf", "/etc/fonts/local.conf"];
+/// Searched as well, for systems without fontconfig.
+const FONT_LOCATIONS: &[&str] = &[
+    "/usr/share/fonts",
+    "/usr/local/share/fonts",
+    "/System/Library/Fonts",
+    "/Library/Fonts",
+    r"C:\Windows\Fonts",
+];
+/// Fonts covering CJK, in the order they are preferred. Each one found is
+/// added to the fallback chain, up to `CJK_FONT_LIMIT`.
+const CJK_FONT_NAMES: &[&str] = &[
+    "notosanscjk-regular.ttc",
+    "notosanscjksc-regular.otf",
+    "notosanscjkjp-regular.otf",
+    "notosanscjkkr-regular.otf",
+    "sourcehansans-regular.ttc",
+    "sourcehansanssc-regular.otf",
+    "wqy-microhei.ttc",
+    "wqy-zenhei.ttc",
+    "droidsansfallbackfull.ttf",
+    "droidsansfallback.ttf",
+    "msyh.ttc",
+    "yugothm.ttc",
+    "msgothic.ttc",
+    "malgun.ttf",
+    "pingfang.ttc",
+    "hiragino sans gb.ttc",
+    "applesdgothicneo.ttc",
+];
+/// CJK fonts are large; a couple cover every script we need.
+const CJK_FONT_LIMIT: usize = 2;
+/// Font files are rarely nested deeper than this below a font directory.
+const MAX_DEPTH: usize = 4;
+
+/// The directories fontconfig is configured with, then the usual locations.
+pub fn font_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+    let mut dirs: Vec<PathBuf> = FONTCONFIG_FILES
+        .iter()
+        .filter_map(|conf| fs::read_to_string(conf).ok())
+        .flat_map(|conf| fontconfig_dirs(&conf, home.as_deref(), data_home.as_deref()))
+        .collect();
+    dirs.extend(FONT_LOCATIONS.iter().map(PathBuf::from));
+    dirs.extend(home.map(|home| home.join(".fonts")));
+    dirs.extend(
+        env::var_os("LOCALAPPDATA")
+            .map(|local| PathBuf::from(local).join(r"Microsoft\Windows\Fonts")),
+    );
+    let mut unique = Vec::new();
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// The `<dir>` entries of a fontconfig file, with `~` and `prefix="xdg"` resolved.
+fn fontconfig_dirs(conf: &str, home: Option<&Path>, data_home: Option<&Path>) -> Vec<PathBuf> {
+    // fonts.conf always has a DOCTYPE, which roxmltree refuses by default.
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let Ok(document) = roxmltree::Document::parse_with_options(conf, options) else {
+        return Vec::new();
+    };
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("dir"))
+        .filter_map(|node| {
+            let dir = node.text()?.trim();
+            match (node.attribute("prefix"), dir.strip_prefix('~')) {
+                (Some("xdg"), _) => Some(data_home?.join(dir)),
+                (_, Some(rest)) => Some(home?.join(rest.trim_start_matches('/'))),
+                _ => Some(PathBuf::from(dir)),
+            }
+        })
+        .collect()
+}
+
+fn collect_fonts(dir: &Path, depth: usize, fonts: &mut HashMap<String, PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Symlinked directories are not followed, they may well loop.
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            if depth < MAX_DEPTH {
+                collect_fonts(&path, depth + 1, fonts);
+            }
+        } else if let Some(name) = path.file_name() {
+            fonts
+                .entry(name.to_string_lossy().to_lowercase())
+                .or_insert(path);
+        }
+    }
+}
+
+/// The CJK fonts found in `dirs`, most preferred first.
+pub fn find_cjk_fonts(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut fonts = HashMap::new();
+    for dir in dirs {
+        collect_fonts(dir, 0, &mut fonts);
+    }
+    CJK_FONT_NAMES
+        .iter()
+        .filter_map(|name| fonts.remove(*name))
+        .take(CJK_FONT_LIMIT)
+        .collect()
+}
+
+/// Reads a font file, refusing anything that is not TrueType or OpenType:
+/// egui panics on font data it cannot parse.
+pub fn read_font(path: &Path) -> Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    match data.get(..4) {
+        Some([0, 1, 0, 0] | b"OTTO" | b"ttcf" | b"true") => Ok(data),
+        _ => Err(anyhow!(
+            "{} is not a TrueType or OpenType font",
+            path.display()
+        )),
+    }
+}
+
+/// The CJK fonts of the system, searched for and read once: changing the
+/// custom font only has to read that one again.
+fn system_fonts() -> &'static [(String, &'static [u8])] {
+    static FONTS: OnceLock<Vec<(String, &'static [u8])>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        find_cjk_fonts(&font_dirs())
+            .into_iter()
+            .filter_map(|path| match read_font(&path) {
+                Ok(data) => {
+                    debug!("Using system font {:?}", path);
+                    // Kept for the rest of the process, like the fonts egui embeds.
+                    Some((path.display().to_string(), &*Vec::leak(data)))
+                }
+                Err(e) => {
+                    debug!("Skipping system font {:?}: {}", path, e);
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+/// Installs `custom` as the preferred font, if set, and the CJK fonts found on
+/// the system as fallbacks after egui's own.
+pub fn install_fonts(ctx: &egui::Context, custom: Option<&Path>) -> Result<()> {
+    let mut definitions = egui::FontDefinitions::default();
+    let mut add = |name: String, data: egui::FontData, preferred: bool| {
+        definitions.font_data.insert(name.clone(), data);
+        for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+            let fonts = definitions.families.entry(family).or_default();
+            match preferred {
+                true => fonts.insert(0, name.clone()),
+                false => fonts.push(name.clone()),
+            }
+        }
+    };
+    for (name, data) in system_fonts() {
+        add(name.clone(), egui::FontData::from_static(data), false);
+    }
+    let mut result = Ok(());
+    if let Some(path) = custom {
+        match read_font(path) {
+            Ok(data) => {
+                debug!("Using custom font {:?}", path);
+                add(
+                    path.display().to_string(),
+                    egui::FontData::from_owned(data),
+                    true,
+                );
+            }
+            Err(e) => result = Err(e),
+        }
+    }
+    ctx.set_fonts(definitions);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::fontconfig_dirs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn should_resolve_fontconfig_dirs() {
+        let conf = r#"<?xml version="1.0"?>
+            <!DOCTYPE fontconfig SYSTEM "urn:fontconfig:fonts.dtd">
+            <fontconfig>
+                <dir>/usr/share/fonts</dir>
+                <dir prefix="xdg">fonts</dir>
+                <dir>~/.fonts</dir>
+            </fontconfig>"#;
+        assert_eq!(
+            fontconfig_dirs(
+                conf,
+                Some(Path::new("/home/a")),
+                Some(Path::new("/home/a/.data"))
+            ),
+            vec![
+                PathBuf::from("/usr/share/fonts"),
+                PathBuf::from("/home/a/.data/fonts"),
+                PathBuf::from("/home/a/.fonts"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::backend::seven_zip_listing;
+use crate::secret::SecretString;
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Text files next to an archive that usually hold its password.
+pub const SIDECAR_NAMES: &[&str] = &["password.txt", "passwords.txt", "pw.txt", "密码.txt"];
+
+/// Where a harvest rule looks for passwords.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum HarvestSource {
+    FileName,
+    SidecarFile,
+    Comment,
+}
+
+impl HarvestSource {
+    pub const ALL: [HarvestSource; 3] = [
+        HarvestSource::FileName,
+        HarvestSource::SidecarFile,
+        HarvestSource::Comment,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HarvestSource::FileName => "File name",
+            HarvestSource::SidecarFile => "Password file next to it",
+            HarvestSource::Comment => "Archive comment",
+        }
+    }
+}
+
+/// A regex run over one source. The first capture group is the password, or
+/// the whole match when the pattern has no group.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarvestRule {
+    pub source: HarvestSource,
+    pub pattern: String,
+    pub enabled: bool,
+    /// Compiled on first use and again once `pattern` changed.
+    #[serde(skip)]
+    compiled: Mutex<Option<(String, Result<Regex, String>)>>,
+}
+
+impl Clone for HarvestRule {
+    fn clone(&self) -> Self {
+        Self {
+            enabled: self.enabled,
+            ..Self::new(self.source, &self.pattern)
+        }
+    }
+}
+
+impl PartialEq for HarvestRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+            && self.pattern == other.pattern
+            && self.enabled == other.enabled
+    }
+}
+
+impl HarvestRule {
+    pub fn new(source: HarvestSource, pattern: &str) -> Self {
+        Self {
+            source,
+            pattern: pattern.to_owned(),
+            enabled: true,
+            compiled: Mutex::default(),
+        }
+    }
+
+    /// The compiled pattern, or why it does not compile.
+    pub fn regex(&self) -> Result<Regex, String> {
+        let mut compiled = self.compiled.lock().unwrap_or_else(|e| e.into_inner());
+        if !matches!(&*compiled, Some((pattern, _)) if *pattern == self.pattern) {
+            *compiled = Some((
+                self.pattern.clone(),
+                Regex::new(&self.pattern).map_err(|e| e.to_string()),
+            ));
+        }
+        compiled.as_ref().unwrap().1.clone()
+    }
+}
+
+pub fn default_rules() -> Vec<HarvestRule> {
+    vec![
+        HarvestRule::new(
+            HarvestSource::FileName,
+            r"(?i)[\[(【（]\s*(?:pw|pwd|pass|password|密码|解压码)\s*[=:：]\s*([^\])】）]+?)\s*[\])】）]",
+        ),
+        HarvestRule::new(
+            HarvestSource::SidecarFile,
+            r"(?im)^\s*(?:(?:pw|pwd|pass|password|密码|解压码)\s*[=:：]\s*)?(\S.*?)\s*$",
+        ),
+        HarvestRule::new(
+            HarvestSource::Comment,
+            r"(?im)(?:pw|pwd|pass|password|密码|解压码)\s*[=:：]\s*(\S+)",
+        ),
+    ]
+}
+
+/// Candidate passwords found around `archive`, in rule order and without
+/// duplicates. Rules with an invalid pattern are skipped. Comments other than
+/// zip ones are read with the 7z at `seven_zip`, if it is set.
+pub fn harvest(rules: &[HarvestRule], archive: &Path, seven_zip: &Path) -> Vec<SecretString> {
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        let Ok(regex) = rule.regex() else {
+            debug!("Skipping invalid harvest pattern for {:?}", rule.source);
+            continue;
+        };
+        for text in source_texts(rule.source, archive, seven_zip) {
+            for captures in regex.captures_iter(&text) {
+                let found = captures.get(1).or_else(|| captures.get(0));
+                let candidate = SecretString::from(found.map_or("", |m| m.as_str()).trim());
+                if !candidate.is_empty() && seen.insert(candidate.clone()) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+    }
+    debug!(
+        "Harvested {} candidate(s) for {:?}",
+        candidates.len(),
+        archive
+    );
+    candidates
+}
+
+fn source_texts(source: HarvestSource, archive: &Path, seven_zip: &Path) -> Vec<String> {
+    match source {
+        HarvestSource::FileName => archive
+            .file_name()
+            .map(|name| vec![name.to_string_lossy().into_owned()])
+            .unwrap_or_default(),
+        HarvestSource::SidecarFile => {
+            let Some(dir) = archive.parent() else {
+                return Vec::new();
+            };
+            SIDECAR_NAMES
+                .iter()
+                .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+                .collect()
+        }
+        HarvestSource::Comment => archive_comment(archive, seven_zip).into_iter().collect(),
+    }
+}
+
+/// Zip comments are read in process, the others, RAR ones above all, by 7z.
+fn archive_comment(archive: &Path, seven_zip: &Path) -> Option<String> {
+    let comment = match zip::ZipArchive::new(fs::File::open(archive).ok()?) {
+        Ok(zip) => String::from_utf8_lossy(zip.comment()).into_owned(),
+        Err(_) if seven_zip.as_os_str().is_empty() => return None,
+        Err(_) => match seven_zip_listing(seven_zip, archive) {
+            Ok(listing) => listing_comment(&listing?)?,
+            Err(e) => {
+                debug!("Could not list {:?} for its comment: {}", archive, e);
+                return None;
+            }
+        },
+    };
+    (!comment.trim().is_empty()).then_some(comment)
+}
+
+/// The `Comment` property of the archive in a `7z l -slt` listing. A comment
+/// of several lines runs until the next property.
+fn listing_comment(listing: &str) -> Option<String> {
+    let property = Regex::new(r"^[A-Z][A-Za-z ]* = ").unwrap();
+    // The archive's properties come before the entries' "----------" line.
+    let mut lines = listing
+        .lines()
+        .take_while(|line| *line != "----------")
+        .skip_while(|line| !line.starts_with("Comment = "));
+    let mut comment = lines.next()?.trim_start_matches("Comment = ").to_owned();
+    for line in lines.take_while(|line| !property.is_match(line)) {
+        comment.push('\n');
+        comment.push_str(line);
+    }
+    Some(comment)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{default_rules, harvest, listing_comment, HarvestSource};
+    use std::path::Path;
+
+    #[test]
+    fn should_harvest_from_file_name() {
+        let found = harvest(
+            &default_rules(),
+            Path::new("/nowhere/foo [pw=abc].rar"),
+            Path::new(""),
+        );
+        assert_eq!(
+            found
+                .iter()
+                .map(|password| password.expose())
+                .collect::<Vec<_>>(),
+            vec!["abc"]
+        );
+    }
+
+    #[test]
+    fn should_harvest_from_sidecar_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("password.txt"), "password: s3cret\n\nother\n").unwrap();
+
+        let rules = default_rules()
+            .into_iter()
+            .filter(|rule| rule.source == HarvestSource::SidecarFile)
+            .collect::<Vec<_>>();
+        let found = harvest(&rules, &dir.join("archive.7z"), Path::new(""));
+        assert_eq!(
+            found
+                .iter()
+                .map(|password| password.expose())
+                .collect::<Vec<_>>(),
+            vec!["s3cret", "other"]
+        );
+    }
+
+    #[test]
+    fn should_read_comment_of_listing() {
+        let listing = "Listing archive: a.rar\n\n--\nPath = a.rar\nType = Rar5\n\
+            Comment = Welcome!\npassword: s3cret\nSolid = -\n\n----------\n\
+            Path = inner.txt\nComment = not the archive's\n";
+        assert_eq!(
+            listing_comment(listing).as_deref(),
+            Some("Welcome!\npassword: s3cret")
+        );
+        assert_eq!(listing_comment("--\nPath = a.rar\n\n----------\n"), None);
+    }
+}
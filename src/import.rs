@@ -0,0 +1,144 @@
+use crate::secret::SecretString;
+use anyhow::Result;
+
+/// Passwords of a KeePass 2 XML export. Old versions kept in an entry's
+/// history are skipped.
+pub fn parse_keepass_xml(xml: &str) -> Result<Vec<SecretString>> {
+    let document = roxmltree::Document::parse(xml)?;
+    Ok(document
+        .descendants()
+        .filter(|node| node.has_tag_name("Entry"))
+        .filter(|entry| {
+            !entry
+                .ancestors()
+                .any(|ancestor| ancestor.has_tag_name("History"))
+        })
+        .filter_map(|entry| {
+            entry
+                .children()
+                .filter(|field| field.has_tag_name("String"))
+                .find(|field| {
+                    field
+                        .children()
+                        .any(|key| key.has_tag_name("Key") && key.text() == Some("Password"))
+                })?
+                .children()
+                .find(|value| value.has_tag_name("Value"))?
+                .text()
+        })
+        .filter(|password| !password.is_empty())
+        .map(SecretString::from)
+        .collect())
+}
+
+/// A CSV file, read as text so any column can be picked. Not `Debug`, the
+/// cells are secrets.
+pub struct CsvTable {
+    /// Empty when the file has no header row.
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    pub fn parse(text: &str, has_headers: bool) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(has_headers)
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let headers = match has_headers {
+            true => reader.headers()?.iter().map(str::to_owned).collect(),
+            false => Vec::new(),
+        };
+        let rows = reader
+            .records()
+            .map(|record| Ok(record?.iter().map(str::to_owned).collect()))
+            .collect::<Result<_>>()?;
+        Ok(Self { headers, rows })
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.headers.len()])
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn column_name(&self, index: usize) -> String {
+        self.headers
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| format!("Column {}", index + 1))
+    }
+
+    /// The column whose header mentions a password, the first one otherwise.
+    pub fn password_column(&self) -> usize {
+        self.headers
+            .iter()
+            .position(|header| header.to_lowercase().contains("password"))
+            .unwrap_or(0)
+    }
+
+    pub fn column(&self, index: usize) -> Vec<SecretString> {
+        self.rows
+            .iter()
+            .filter_map(|row| row.get(index))
+            .filter(|cell| !cell.is_empty())
+            .map(|cell| cell.as_str().into())
+            .collect()
+    }
+}
+
+/// Splits pasted text by `delimiter`, or by line when it is empty.
+pub fn split_text(text: &str, delimiter: &str) -> Vec<SecretString> {
+    let parts: Box<dyn Iterator<Item = &str>> = match delimiter {
+        "" => Box::new(text.lines()),
+        delimiter => Box::new(text.split(delimiter)),
+    };
+    parts
+        .map(|part| part.trim_matches(['\r', '\n']))
+        .filter(|part| !part.is_empty())
+        .map(SecretString::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_keepass_xml, split_text, CsvTable};
+
+    fn exposed(passwords: Vec<crate::secret::SecretString>) -> Vec<String> {
+        passwords
+            .iter()
+            .map(|password| password.expose().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn should_parse_keepass_export_without_history() {
+        let xml = r#"<KeePassFile><Root><Group>
+            <Entry>
+                <String><Key>Title</Key><Value>forum</Value></String>
+                <String><Key>Password</Key><Value ProtectedInMemory="True">current</Value></String>
+                <History><Entry>
+                    <String><Key>Password</Key><Value>old</Value></String>
+                </Entry></History>
+            </Entry>
+            <Entry><String><Key>Password</Key><Value/></String></Entry>
+        </Group></Root></KeePassFile>"#;
+        assert_eq!(exposed(parse_keepass_xml(xml).unwrap()), vec!["current"]);
+    }
+
+    #[test]
+    fn should_pick_csv_password_column() {
+        let table = CsvTable::parse("site,Password\nA,\"x,y\"\nB,\n", true).unwrap();
+        assert_eq!(table.password_column(), 1);
+        assert_eq!(exposed(table.column(1)), vec!["x,y"]);
+    }
+
+    #[test]
+    fn should_split_pasted_text() {
+        assert_eq!(exposed(split_text("a\r\nb\n\n", "")), vec!["a", "b"]);
+        assert_eq!(exposed(split_text("a b|c\n", "|")), vec!["a b", "c"]);
+    }
+}
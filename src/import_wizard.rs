@@ -0,0 +1,281 @@
+use crate::import::{parse_keepass_xml, split_text, CsvTable};
+use crate::passwords::{Dictionary, SanitizeRules};
+use crate::secret::SecretString;
+use egui::{Color32, ScrollArea};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ImportKind {
+    KeePass,
+    Csv,
+    Text,
+}
+
+impl ImportKind {
+    const ALL: [ImportKind; 3] = [ImportKind::KeePass, ImportKind::Csv, ImportKind::Text];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ImportKind::KeePass => "KeePass 2 XML",
+            ImportKind::Csv => "CSV",
+            ImportKind::Text => "Text or another password list",
+        }
+    }
+
+    /// Recorded as the source of the imported entries.
+    fn source(&self) -> &'static str {
+        match self {
+            ImportKind::KeePass => "KeePass",
+            ImportKind::Csv => "CSV",
+            ImportKind::Text => "Import",
+        }
+    }
+}
+
+/// UI state of the import dialog. The dictionary itself is owned by the app.
+pub struct ImportWizard {
+    kind: ImportKind,
+    file: Option<PathBuf>,
+    /// Pasted text, or the contents of `file`.
+    text: SecretString,
+    /// Splits pasted text; empty means one password per line.
+    delimiter: String,
+    has_headers: bool,
+    column: usize,
+    csv: Option<CsvTable>,
+    /// Passwords found in the input, or why it could not be read.
+    parsed: Result<Vec<SecretString>, String>,
+}
+
+impl fmt::Debug for ImportWizard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImportWizard")
+            .field("kind", &self.kind)
+            .field("file", &self.file)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Passwords the user chose to import.
+pub struct Imported {
+    pub passwords: Vec<SecretString>,
+    pub source: &'static str,
+}
+
+/// How a parsed line relates to the dictionary and to the lines before it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum LineStatus {
+    New,
+    Exists,
+    Repeated,
+}
+
+impl ImportWizard {
+    pub fn new() -> Self {
+        Self {
+            kind: ImportKind::Text,
+            file: None,
+            text: SecretString::default(),
+            delimiter: String::new(),
+            has_headers: true,
+            column: 0,
+            csv: None,
+            parsed: Ok(Vec::new()),
+        }
+    }
+
+    fn load_file(&mut self, path: PathBuf) {
+        match fs::read_to_string(&path) {
+            Ok(text) => self.text = text.into(),
+            Err(e) => {
+                self.parsed = Err(format!("Could not read {}: {}", path.display(), e));
+                return;
+            }
+        }
+        self.file = Some(path);
+        self.reparse(true);
+    }
+
+    fn reparse(&mut self, pick_column: bool) {
+        let text = self.text.expose();
+        if text.trim().is_empty() {
+            self.csv = None;
+            self.parsed = Ok(Vec::new());
+            return;
+        }
+        self.parsed = match self.kind {
+            ImportKind::KeePass => parse_keepass_xml(text).map_err(|e| e.to_string()),
+            ImportKind::Text => Ok(split_text(text, &self.delimiter)),
+            ImportKind::Csv => match CsvTable::parse(text, self.has_headers) {
+                Ok(table) => {
+                    if pick_column || self.column >= table.column_count() {
+                        self.column = table.password_column();
+                    }
+                    let passwords = table.column(self.column);
+                    self.csv = Some(table);
+                    Ok(passwords)
+                }
+                Err(e) => {
+                    self.csv = None;
+                    Err(e.to_string())
+                }
+            },
+        };
+    }
+
+    /// Returns the new passwords once the user confirms the import.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        dictionary: &Dictionary,
+        rules: &SanitizeRules,
+    ) -> Option<Imported> {
+        ui.horizontal(|ui| {
+            for kind in ImportKind::ALL {
+                if ui
+                    .selectable_value(&mut self.kind, kind, kind.name())
+                    .changed()
+                {
+                    self.reparse(true);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            match &self.file {
+                Some(file) => ui.label(file.display().to_string()),
+                None => ui.weak("none"),
+            };
+            if ui.button("Browse").clicked() {
+                let dialog = match self.kind {
+                    ImportKind::KeePass => rfd::FileDialog::new().add_filter("XML", &["xml"]),
+                    ImportKind::Csv => rfd::FileDialog::new().add_filter("CSV", &["csv"]),
+                    ImportKind::Text => rfd::FileDialog::new(),
+                };
+                if let Some(path) = dialog.pick_file() {
+                    self.load_file(path);
+                }
+            }
+        });
+        match self.kind {
+            ImportKind::KeePass => {}
+            ImportKind::Csv => {
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut self.has_headers, "First row is a header")
+                        .changed()
+                    {
+                        self.reparse(true);
+                    }
+                    if let Some(table) = &self.csv {
+                        let mut column = self.column;
+                        egui::ComboBox::from_id_source("import_csv_column")
+                            .selected_text(table.column_name(column))
+                            .show_ui(ui, |ui| {
+                                for index in 0..table.column_count() {
+                                    ui.selectable_value(
+                                        &mut column,
+                                        index,
+                                        table.column_name(index),
+                                    );
+                                }
+                            });
+                        if column != self.column {
+                            self.column = column;
+                            self.reparse(false);
+                        }
+                    }
+                });
+            }
+            ImportKind::Text => {
+                ui.horizontal(|ui| {
+                    ui.label("Delimiter:");
+                    let delimiter = egui::TextEdit::singleline(&mut self.delimiter)
+                        .hint_text("new line")
+                        .desired_width(60.0);
+                    if ui.add(delimiter).changed() {
+                        self.reparse(false);
+                    }
+                });
+                let pasted = egui::TextEdit::multiline(self.text.expose_mut())
+                    .hint_text("Paste passwords here")
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY);
+                if ui.add(pasted).changed() {
+                    self.reparse(false);
+                }
+            }
+        }
+        ui.separator();
+
+        let parsed = match &self.parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                ui.colored_label(Color32::RED, e);
+                return None;
+            }
+        };
+        let existing = dictionary
+            .passwords(rules)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let key = |password: &SecretString| match rules.trim {
+            true => password.expose().trim().into(),
+            false => password.clone(),
+        };
+        // Each line is new, already in the dictionary, or a repeat of an earlier line.
+        let mut seen = HashSet::new();
+        let statuses = parsed
+            .iter()
+            .map(|password| {
+                let key = key(password);
+                (!existing.contains(&key), seen.insert(key))
+            })
+            .map(|line| match line {
+                (_, false) => LineStatus::Repeated,
+                (true, true) => LineStatus::New,
+                (false, true) => LineStatus::Exists,
+            })
+            .collect::<Vec<_>>();
+        let count = |status| statuses.iter().filter(|line| **line == status).count();
+        let new = parsed
+            .iter()
+            .zip(&statuses)
+            .filter(|(_, status)| **status == LineStatus::New)
+            .map(|(password, _)| password.clone())
+            .collect::<Vec<_>>();
+        ui.label(format!(
+            "{} new, {} already in the dictionary, {} repeated in the file",
+            new.len(),
+            count(LineStatus::Exists),
+            count(LineStatus::Repeated)
+        ));
+        let row_height = ui.spacing().interact_size.y;
+        ScrollArea::vertical()
+            .max_height(240.0)
+            .auto_shrink([false, true])
+            .show_rows(ui, row_height, parsed.len(), |ui, range| {
+                for (password, status) in parsed[range.clone()].iter().zip(&statuses[range]) {
+                    ui.horizontal(|ui| {
+                        match status {
+                            LineStatus::New => ui.colored_label(Color32::GREEN, "new"),
+                            LineStatus::Exists => ui.weak("exists"),
+                            LineStatus::Repeated => ui.weak("repeat"),
+                        };
+                        ui.label(password.expose());
+                    });
+                }
+            });
+        let import = egui::Button::new(format!("Import {} passwords", new.len()));
+        if ui.add_enabled(!new.is_empty(), import).clicked() {
+            return Some(Imported {
+                passwords: new,
+                source: self.kind.source(),
+            });
+        }
+        None
+    }
+}
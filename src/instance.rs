@@ -0,0 +1,407 @@
+use anyhow::Result;
+use log::debug;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Whether this process is the window, or handed its files to the running one.
+pub enum Instance {
+    /// Another window took the files; this process has nothing left to do.
+    Forwarded,
+    /// No window was running. Later launches send their files here.
+    First(Listener),
+}
+
+/// Receives the files of later launches. On Unix it is a socket next to a
+/// lock file, both in a directory only this user can use. Whoever holds the
+/// lock is the window; the socket only carries the files.
+///
+/// Elsewhere the window listens on a loopback port. Whoever creates the port
+/// file in the user's local data directory is the window. The file also
+/// holds a random token, and launches prove they could read it before the
+/// window takes their files, since any local user can connect to the port.
+pub struct Listener(Transport);
+
+enum Transport {
+    #[cfg(unix)]
+    Socket {
+        socket: std::os::unix::net::UnixListener,
+        /// Locked for as long as the window runs.
+        _lock: std::fs::File,
+    },
+    #[cfg(any(not(unix), test))]
+    Loopback {
+        socket: std::net::TcpListener,
+        token: String,
+    },
+}
+
+impl Instance {
+    /// Sends `paths` to the running window if there is one, or becomes the one
+    /// later launches send to.
+    #[cfg(unix)]
+    pub fn claim(paths: &[PathBuf]) -> Result<Self> {
+        claim_in(&runtime_dir()?, paths)
+    }
+
+    #[cfg(not(unix))]
+    pub fn claim(paths: &[PathBuf]) -> Result<Self> {
+        let dir = std::env::var_os("LOCALAPPDATA")
+            .map(|dir| PathBuf::from(dir).join(crate::APP_NAME))
+            .ok_or_else(|| anyhow::anyhow!("LOCALAPPDATA is not set"))?;
+        std::fs::create_dir_all(&dir)?;
+        claim_loopback_in(&dir, paths)
+    }
+}
+
+/// `XDG_RUNTIME_DIR` if set, which is private to the user already. Otherwise
+/// a directory of our own in the shared temporary directory, which must be
+/// ours and closed to everyone else: another user could have created it first.
+#[cfg(unix)]
+fn runtime_dir() -> Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    // SAFETY: getuid cannot fail and touches no memory of ours.
+    let uid = unsafe { libc::getuid() };
+    let dir = std::env::temp_dir().join(format!("wpass-gui-{}", uid));
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+    let metadata = std::fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(anyhow::anyhow!(
+            "{:?} is not a private directory of this user",
+            dir
+        ));
+    }
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn claim_in(dir: &Path, paths: &[PathBuf]) -> Result<Instance> {
+    use std::io::{ErrorKind, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let socket = dir.join("wpass-gui.sock");
+    let lock = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join("wpass-gui.lock"))?;
+    // SAFETY: the descriptor belongs to `lock`, which outlives the call.
+    if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        // A socket left behind by a window that did not shut down cleanly.
+        match std::fs::remove_file(&socket) {
+            Ok(()) => debug!("Removed stale socket {:?}", socket),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        return Ok(Instance::First(Listener(Transport::Socket {
+            socket: UnixListener::bind(&socket)?,
+            _lock: lock,
+        })));
+    }
+    let error = std::io::Error::last_os_error();
+    if error.kind() != ErrorKind::WouldBlock {
+        return Err(error.into());
+    }
+    // The window may hold the lock but not listen yet when both start at once.
+    let mut attempts = 0;
+    let mut stream = loop {
+        match UnixStream::connect(&socket) {
+            Ok(stream) => break stream,
+            Err(e) if attempts < 20 => {
+                debug!("The running window does not listen yet: {}", e);
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    debug!("Handing {} file(s) to the running window", paths.len());
+    stream.write_all(&serde_json::to_vec(&crate::archive::absolute_paths(paths))?)?;
+    Ok(Instance::Forwarded)
+}
+
+#[cfg(any(not(unix), test))]
+fn claim_loopback_in(dir: &Path, paths: &[PathBuf]) -> Result<Instance> {
+    use std::io::{ErrorKind, Write};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    let port_file = dir.join("wpass-gui.port");
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&port_file)
+        {
+            Ok(mut file) => {
+                // Listen before the port is written, so a port nobody answers
+                // on always means the window is gone.
+                let socket = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+                let token = random_hex();
+                writeln!(file, "{} {}", socket.local_addr()?.port(), token)?;
+                return Ok(Instance::First(Listener(Transport::Loopback {
+                    socket,
+                    token,
+                })));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        let stale = match std::fs::read_to_string(&port_file) {
+            Ok(contents) => match parse_port_file(&contents) {
+                Some((port, token)) => match forward(port, token, paths)? {
+                    true => return Ok(Instance::Forwarded),
+                    false => true,
+                },
+                // The window may have created the file but not written it yet
+                // when both start at once. Half a file that stays is a crash.
+                None => attempts >= 5,
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+        if stale {
+            debug!("Removing stale port file {:?}", port_file);
+            match std::fs::remove_file(&port_file) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if attempts >= 20 {
+            return Err(anyhow::anyhow!("{:?} was never written", port_file));
+        } else {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+#[cfg(any(not(unix), test))]
+fn parse_port_file(contents: &str) -> Option<(u16, &str)> {
+    let (port, token) = contents.strip_suffix('\n')?.split_once(' ')?;
+    Some((port.parse().ok()?, token))
+}
+
+/// Hands `paths` to the window on `port`. False if nothing that knows the
+/// handshake answers there, so the port file is left over from a crash.
+#[cfg(any(not(unix), test))]
+fn forward(port: u16, token: &str, paths: &[PathBuf]) -> Result<bool> {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{Ipv4Addr, Shutdown, TcpStream};
+
+    let address = (Ipv4Addr::LOCALHOST, port).into();
+    let stream = match TcpStream::connect_timeout(&address, Duration::from_secs(1)) {
+        Ok(stream) => stream,
+        Err(e) => {
+            debug!("Nothing listens on port {}: {}", port, e);
+            return Ok(false);
+        }
+    };
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut challenge = String::new();
+    BufReader::new((&stream).take(64)).read_line(&mut challenge)?;
+    let Some(challenge) = challenge.strip_suffix('\n') else {
+        debug!("Port {} does not belong to a window", port);
+        return Ok(false);
+    };
+    debug!("Handing {} file(s) to the running window", paths.len());
+    let mut message = format!("{}\n", proof(token, challenge)).into_bytes();
+    message.extend(serde_json::to_vec(&crate::archive::absolute_paths(paths))?);
+    (&stream).write_all(&message)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut answer = Vec::new();
+    (&stream).read_to_end(&mut answer)?;
+    match answer == b"ok" {
+        true => Ok(true),
+        false => Err(anyhow::anyhow!("The running window refused the files")),
+    }
+}
+
+#[cfg(any(not(unix), test))]
+fn random_hex() -> String {
+    use chacha20poly1305::aead::rand_core::RngCore;
+
+    let mut bytes = [0u8; 16];
+    chacha20poly1305::aead::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// What a launch that read `token` answers to `challenge`.
+#[cfg(any(not(unix), test))]
+fn proof(token: &str, challenge: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(challenge.as_bytes());
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+impl Listener {
+    /// Receives files in the background and wakes `ctx` for each launch. The
+    /// list is empty when a launch only wants the window raised.
+    pub fn spawn(self, ctx: egui::Context) -> mpsc::Receiver<Vec<PathBuf>> {
+        let (tx, rx) = mpsc::channel();
+        // All of it, the lock has to be held as long as the socket is served.
+        let listener = self;
+        std::thread::spawn(move || match &listener.0 {
+            #[cfg(unix)]
+            Transport::Socket { socket, .. } => serve(socket.incoming(), receive, &tx, &ctx),
+            #[cfg(any(not(unix), test))]
+            Transport::Loopback { socket, token } => serve(
+                socket.incoming(),
+                |stream| receive_loopback(stream, token),
+                &tx,
+                &ctx,
+            ),
+        });
+        rx
+    }
+}
+
+fn serve<S>(
+    incoming: impl Iterator<Item = std::io::Result<S>>,
+    mut receive: impl FnMut(S) -> Result<Vec<PathBuf>>,
+    tx: &mpsc::Sender<Vec<PathBuf>>,
+    ctx: &egui::Context,
+) {
+    for stream in incoming {
+        match stream.map_err(anyhow::Error::from).and_then(&mut receive) {
+            Ok(paths) => {
+                if tx.send(paths).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+            Err(e) => debug!("Could not receive from a launch: {}", e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn receive(mut stream: std::os::unix::net::UnixStream) -> Result<Vec<PathBuf>> {
+    use std::io::Read;
+
+    // A launch that never finishes writing must not block the others.
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut message = Vec::new();
+    stream.read_to_end(&mut message)?;
+    Ok(serde_json::from_slice(&message)?)
+}
+
+#[cfg(any(not(unix), test))]
+fn receive_loopback(mut stream: std::net::TcpStream, token: &str) -> Result<Vec<PathBuf>> {
+    use std::io::{Read, Write};
+
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+    let challenge = random_hex();
+    stream.write_all(format!("{}\n", challenge).as_bytes())?;
+    let mut message = Vec::new();
+    (&mut stream).take(1 << 20).read_to_end(&mut message)?;
+    let (answer, paths) = message
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map(|end| (&message[..end], &message[end + 1..]))
+        .ok_or_else(|| anyhow::anyhow!("Malformed message"))?;
+    if answer != proof(token, &challenge).as_bytes() {
+        return Err(anyhow::anyhow!("The launch does not know the token"));
+    }
+    let paths = serde_json::from_slice(paths)?;
+    stream.write_all(b"ok")?;
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{claim_loopback_in, Instance};
+    use std::path::PathBuf;
+
+    #[cfg(unix)]
+    #[test]
+    fn should_forward_paths_to_first_instance() {
+        let temp = tempfile::tempdir().unwrap();
+        let Instance::First(listener) = super::claim_in(temp.path(), &[]).unwrap() else {
+            panic!("no window is running yet");
+        };
+        let received = listener.spawn(egui::Context::default());
+
+        let paths = vec![PathBuf::from("/downloads/a.rar"), PathBuf::from("/b c.7z")];
+        assert!(matches!(
+            super::claim_in(temp.path(), &paths).unwrap(),
+            Instance::Forwarded
+        ));
+        assert_eq!(received.recv().unwrap(), paths);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_take_over_after_the_window_is_gone() {
+        let temp = tempfile::tempdir().unwrap();
+        let Instance::First(listener) = super::claim_in(temp.path(), &[]).unwrap() else {
+            panic!("no window is running yet");
+        };
+        // The lock goes with the window, the socket file stays behind.
+        drop(listener);
+        assert!(temp.path().join("wpass-gui.sock").exists());
+        assert!(matches!(
+            super::claim_in(temp.path(), &[]).unwrap(),
+            Instance::First(_)
+        ));
+    }
+
+    #[test]
+    fn should_forward_paths_over_loopback() {
+        let temp = tempfile::tempdir().unwrap();
+        let Instance::First(listener) = claim_loopback_in(temp.path(), &[]).unwrap() else {
+            panic!("no window is running yet");
+        };
+        let received = listener.spawn(egui::Context::default());
+
+        let paths = vec![PathBuf::from("/downloads/a.rar"), PathBuf::from("/b c.7z")];
+        assert!(matches!(
+            claim_loopback_in(temp.path(), &paths).unwrap(),
+            Instance::Forwarded
+        ));
+        assert_eq!(received.recv().unwrap(), paths);
+    }
+
+    #[test]
+    fn should_take_over_port_file_nobody_answers() {
+        let temp = tempfile::tempdir().unwrap();
+        let Instance::First(listener) = claim_loopback_in(temp.path(), &[]).unwrap() else {
+            panic!("no window is running yet");
+        };
+        drop(listener);
+        assert!(temp.path().join("wpass-gui.port").exists());
+        assert!(matches!(
+            claim_loopback_in(temp.path(), &[]).unwrap(),
+            Instance::First(_)
+        ));
+    }
+
+    #[test]
+    fn should_refuse_launch_without_token() {
+        let temp = tempfile::tempdir().unwrap();
+        let Instance::First(listener) = claim_loopback_in(temp.path(), &[]).unwrap() else {
+            panic!("no window is running yet");
+        };
+        let received = listener.spawn(egui::Context::default());
+        let contents = std::fs::read_to_string(temp.path().join("wpass-gui.port")).unwrap();
+        let (port, _) = super::parse_port_file(&contents).unwrap();
+
+        assert!(super::forward(port, "guessed", &[PathBuf::from("/a.rar")]).is_err());
+        assert!(received
+            .recv_timeout(std::time::Duration::from_millis(200))
+            .is_err());
+    }
+}
@@ -1,3 +1,28 @@
 mod app;
+mod archive;
+mod backend;
+mod backup;
+mod cli;
+mod codepage;
+mod desktop;
+mod fingerprint;
+mod fonts;
+mod harvest;
+mod import;
+mod import_wizard;
+mod instance;
+mod mojibake;
+mod password_editor;
+mod passwords;
+mod probe;
+mod rules;
+mod secret;
+mod settings;
+mod usage;
+mod vault;
+mod watcher;
 mod worker;
-pub use app::WPassApp;
\ No newline at end of file
+pub use app::WPassApp;
+pub use cli::Cli;
+pub use instance::Instance;
+pub use settings::APP_NAME;
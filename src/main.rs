@@ -1,8 +1,27 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 fn main() -> eframe::Result<()> {
+    #[cfg(windows)]
+    attach_parent_console();
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    let cli = wpass_gui::Cli::parse_launch();
+    if cli.command.is_some() {
+        std::process::exit(cli.run().into());
+    }
+    let files = cli.files;
+    let listener = match cli.new_window {
+        true => None,
+        false => match wpass_gui::Instance::claim(&files) {
+            Ok(wpass_gui::Instance::Forwarded) => return Ok(()),
+            Ok(wpass_gui::Instance::First(listener)) => Some(listener),
+            Err(e) => {
+                log::debug!("Cannot tell whether a window is already open: {}", e);
+                None
+            }
+        },
+    };
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([400.0, 300.0])
@@ -11,12 +30,32 @@ fn main() -> eframe::Result<()> {
         ..Default::default()
     };
     eframe::run_native(
-        "Wpass GUI",
+        wpass_gui::APP_NAME,
         native_options,
         Box::new(|cc| {
             let mut wpass_gui = wpass_gui::WPassApp::new(cc);
             wpass_gui.init();
+            wpass_gui.open_paths(files);
+            if let Some(listener) = listener {
+                wpass_gui.listen(listener, &cc.egui_ctx);
+            }
             Box::new(wpass_gui)
         }),
     )
 }
+
+/// Release builds have no console of their own, so the output of the commands
+/// and of `--help` goes to the console they were started from, if any.
+#[cfg(windows)]
+fn attach_parent_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // SAFETY: AttachConsole takes no pointers and fails harmlessly when the
+    // parent has no console, e.g. when started from Explorer.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::codepage::LegacyEncoding;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Codepages file names are re-decoded from, most common first so they win ties.
+pub const NAME_ENCODINGS: [LegacyEncoding; 4] = [
+    LegacyEncoding::Gbk,
+    LegacyEncoding::ShiftJis,
+    LegacyEncoding::Big5,
+    LegacyEncoding::EucKr,
+];
+
+/// Whether `c` is something a file name written in one of `NAME_ENCODINGS`
+/// would contain. Box drawing, Greek and half-width katakana usually mean
+/// the bytes were decoded with the wrong codepage.
+fn is_plausible(c: char) -> bool {
+    matches!(c,
+        ' '..='~'
+        | '\u{2010}'..='\u{2027}'
+        | '\u{3000}'..='\u{30FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{FF01}'..='\u{FF5E}')
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}')
+}
+
+fn reads_cleanly(bytes: &[u8], encoding: LegacyEncoding) -> bool {
+    encoding
+        .decode(bytes)
+        .is_some_and(|decoded| decoded.chars().all(is_plausible))
+}
+
+/// The bytes of a name that is not UTF-8. Tools that do not decode names at
+/// all write the archive's bytes as they are.
+#[cfg(unix)]
+fn non_utf8_bytes(name: &OsStr) -> Option<&[u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    name.to_str().is_none().then(|| name.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn non_utf8_bytes(_name: &OsStr) -> Option<&[u8]> {
+    None
+}
+
+/// Tools decode names without the ZIP UTF-8 flag as CP437, which is how
+/// GBK names end up as `µû░σ╗║`, or keep their bytes. Accented Latin names
+/// like `café` stay alone, their bytes read as nothing sensible in the other
+/// codepages.
+fn is_garbled(name: &OsStr) -> bool {
+    if let Some(bytes) = non_utf8_bytes(name) {
+        return NAME_ENCODINGS
+            .iter()
+            .any(|encoding| reads_cleanly(bytes, *encoding));
+    }
+    let Some(name) = name.to_str() else {
+        return false;
+    };
+    if name.is_ascii() || name.chars().all(is_plausible) {
+        return false;
+    }
+    LegacyEncoding::Cp437.encode(name).is_some_and(|bytes| {
+        NAME_ENCODINGS
+            .iter()
+            .any(|encoding| reads_cleanly(&bytes, *encoding))
+    })
+}
+
+/// The bytes behind a name as it was extracted.
+fn raw_name(name: &OsStr) -> Option<Vec<u8>> {
+    match non_utf8_bytes(name) {
+        Some(bytes) => Some(bytes.to_vec()),
+        None => LegacyEncoding::Cp437.encode(name.to_str()?),
+    }
+}
+
+/// Entries, relative to the output directory, with a garbled name somewhere in their path.
+pub fn find_garbled(entries: &[PathBuf]) -> Vec<PathBuf> {
+    entries
+        .iter()
+        .filter(|entry| entry.iter().any(is_garbled))
+        .cloned()
+        .collect()
+}
+
+/// The codepage under which the garbled names read best, if any reads cleanly.
+pub fn detect(entries: &[PathBuf]) -> Option<LegacyEncoding> {
+    let names = entries
+        .iter()
+        .flat_map(|entry| entry.iter())
+        .filter(|name| is_garbled(name))
+        .collect::<BTreeSet<_>>();
+    let raw = names
+        .iter()
+        .map(|name| raw_name(name))
+        .collect::<Option<Vec<_>>>()?;
+    NAME_ENCODINGS
+        .iter()
+        .filter_map(|encoding| {
+            let decoded = raw
+                .iter()
+                .map(|bytes| encoding.decode(bytes))
+                .collect::<Option<String>>()
+                .filter(|decoded| decoded.chars().all(is_plausible))?;
+            // Shift-JIS names read as GBK come out as rare but valid hanzi,
+            // so kana are what tells Japanese apart.
+            let score = match encoding {
+                LegacyEncoding::ShiftJis => decoded.chars().filter(|c| is_kana(*c)).count(),
+                _ => 0,
+            };
+            Some((*encoding, score))
+        })
+        .fold(
+            None,
+            |best: Option<(LegacyEncoding, usize)>, candidate| match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            },
+        )
+        .map(|(encoding, _)| encoding)
+}
+
+/// The extracted `name` decoded with `encoding`, or as extracted for `None`,
+/// if it is not garbled or does not convert cleanly.
+fn convert_name(name: &OsStr, encoding: Option<LegacyEncoding>) -> OsString {
+    let Some(encoding) = encoding.filter(|_| is_garbled(name)) else {
+        return name.to_owned();
+    };
+    raw_name(name)
+        .and_then(|bytes| encoding.decode(&bytes))
+        .filter(|converted| !converted.contains(['/', '\\', '\0']))
+        .map(OsString::from)
+        .unwrap_or_else(|| name.to_owned())
+}
+
+fn convert_path(entry: &Path, encoding: Option<LegacyEncoding>) -> PathBuf {
+    entry
+        .iter()
+        .map(|name| convert_name(name, encoding))
+        .collect()
+}
+
+/// Renames the entries under `output`, named as extracted but currently
+/// decoded with `from`, to their names decoded with `to`. `None` stands for
+/// the names as extracted. Returns the entries as they are named now. Nothing
+/// is renamed onto an existing file.
+pub fn redecode(
+    output: &Path,
+    entries: &[PathBuf],
+    from: Option<LegacyEncoding>,
+    to: Option<LegacyEncoding>,
+) -> Result<Vec<PathBuf>> {
+    // Parent directories are renamed too, even when the archive did not list them.
+    let mut all = entries
+        .iter()
+        .flat_map(|entry| entry.ancestors())
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect::<Vec<_>>();
+    all.sort();
+    all.dedup();
+    // Children first, so their parents still have the old name.
+    all.sort_by_key(|entry| std::cmp::Reverse(entry.components().count()));
+    let renames = all
+        .iter()
+        .filter_map(|entry| {
+            let name = entry.file_name()?;
+            let (old, new) = (convert_name(name, from), convert_name(name, to));
+            let parent = output.join(convert_path(entry.parent()?, from));
+            (old != new).then(|| (parent.join(old), parent.join(new)))
+        })
+        .collect::<Vec<_>>();
+    let taken = |path: &Path| fs::symlink_metadata(path).is_ok();
+    // Checked up front too, so a clash does not leave the names half converted.
+    if let Some((_, new)) = renames.iter().find(|(_, new)| taken(new)) {
+        return Err(anyhow!("{:?} already exists", new));
+    }
+    for (old, new) in &renames {
+        if taken(new) {
+            return Err(anyhow!("{:?} already exists", new));
+        }
+        if taken(old) {
+            fs::rename(old, new)?;
+        }
+    }
+    Ok(entries
+        .iter()
+        .map(|entry| convert_path(entry, to))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect, find_garbled, redecode};
+    use crate::codepage::LegacyEncoding;
+    use std::path::PathBuf;
+
+    /// How a tool without UTF-8 support shows `name` stored in `encoding`.
+    fn garble(name: &str, encoding: LegacyEncoding) -> String {
+        LegacyEncoding::Cp437
+            .decode(&encoding.encode(name).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn should_detect_and_rename_garbled_names() {
+        assert_eq!(garble("新建", LegacyEncoding::Gbk), "╨┬╜¿");
+        let entries = vec![
+            PathBuf::from(garble("新建文件夹", LegacyEncoding::Gbk)),
+            PathBuf::from(format!(
+                "{}/readme.txt",
+                garble("新建文件夹", LegacyEncoding::Gbk)
+            )),
+            PathBuf::from("plain.txt"),
+            PathBuf::from("café.txt"),
+        ];
+        let garbled = find_garbled(&entries);
+        assert_eq!(garbled, entries[..2]);
+        assert_eq!(detect(&garbled), Some(LegacyEncoding::Gbk));
+        let japanese = vec![PathBuf::from(garble(
+            "テスト.txt",
+            LegacyEncoding::ShiftJis,
+        ))];
+        assert_eq!(detect(&japanese), Some(LegacyEncoding::ShiftJis));
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join(&entries[0])).unwrap();
+        std::fs::write(dir.join(&entries[1]), "hi").unwrap();
+        let renamed = redecode(dir, &garbled, None, Some(LegacyEncoding::Gbk)).unwrap();
+        assert_eq!(renamed[1], PathBuf::from("新建文件夹/readme.txt"));
+        assert!(dir.join(&renamed[1]).is_file());
+
+        let restored = redecode(dir, &garbled, Some(LegacyEncoding::Gbk), None).unwrap();
+        assert_eq!(restored, garbled);
+        assert!(dir.join(&restored[1]).is_file());
+
+        // A file that already has the converted name is left alone.
+        std::fs::write(dir.join("新建文件夹"), "mine").unwrap();
+        assert!(redecode(dir, &garbled, None, Some(LegacyEncoding::Gbk)).is_err());
+        assert!(dir.join(&garbled[1]).is_file());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("新建文件夹")).unwrap(),
+            "mine"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_rename_names_kept_as_raw_bytes() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let gbk = LegacyEncoding::Gbk.encode("新建.txt").unwrap();
+        let entries = vec![PathBuf::from(OsStr::from_bytes(&gbk))];
+        assert_eq!(find_garbled(&entries), entries);
+        assert_eq!(detect(&entries), Some(LegacyEncoding::Gbk));
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join(&entries[0]), "hi").unwrap();
+        let renamed = redecode(dir, &entries, None, Some(LegacyEncoding::Gbk)).unwrap();
+        assert_eq!(renamed, vec![PathBuf::from("新建.txt")]);
+        assert!(dir.join("新建.txt").is_file());
+        assert_eq!(
+            redecode(dir, &entries, Some(LegacyEncoding::Gbk), None).unwrap(),
+            entries
+        );
+        assert!(dir.join(&entries[0]).is_file());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::passwords::Dictionary;
+use crate::secret::SecretString;
+use crate::usage::UsageStore;
+use chrono::{DateTime, Local, Utc};
+use egui::{scroll_area::ScrollBarVisibility, ScrollArea};
+use std::collections::BTreeSet;
+
+/// UI state of the password panel. The dictionary itself is owned by the app.
+#[derive(Debug, Default)]
+pub struct PasswordEditor {
+    search: String,
+    new_password: SecretString,
+    /// Indices into `Dictionary::entries`.
+    selected: BTreeSet<usize>,
+    /// Whether the password being edited inline has changed since it got focus.
+    editing_changed: bool,
+    /// Text of the tags field being edited, committed when it loses focus.
+    editing_tags: Option<(usize, String)>,
+}
+
+impl PasswordEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indices are invalidated whenever the dictionary is reordered.
+    pub fn clear_selection(&mut self) {
+        self.selected.clear();
+        self.editing_tags = None;
+    }
+
+    /// Returns true when an edit has been completed and the dictionary should be saved.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        dictionary: &mut Dictionary,
+        usage: &UsageStore,
+    ) -> bool {
+        let mut committed = false;
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            ui.add(egui::TextEdit::singleline(&mut self.search).desired_width(f32::INFINITY));
+        });
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(self.new_password.expose_mut())
+                    .hint_text("New password")
+                    .desired_width(ui.available_width() / 2.0),
+            );
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (ui.button("Add").clicked() || submitted) && !self.new_password.is_empty() {
+                dictionary.push(std::mem::take(&mut self.new_password), "Manual");
+                committed = true;
+            }
+            let delete = egui::Button::new(format!("Delete {} selected", self.selected.len()));
+            if ui.add_enabled(!self.selected.is_empty(), delete).clicked() {
+                for index in self.selected.iter().rev() {
+                    dictionary.entries.remove(*index);
+                }
+                self.selected.clear();
+                committed = true;
+            }
+        });
+        ui.separator();
+
+        let needle = self.search.to_lowercase();
+        let visible = dictionary
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                needle.is_empty()
+                    || entry.password.expose().to_lowercase().contains(&needle)
+                    || entry.metadata.note.to_lowercase().contains(&needle)
+                    || entry
+                        .metadata
+                        .tags
+                        .iter()
+                        .any(|tag| tag.to_lowercase().contains(&needle))
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let width = ui.available_width();
+        let password_width = width * 0.25;
+        let tags_width = width * 0.14;
+        let note_width = width * 0.16;
+        let source_width = width * 0.1;
+        let hits_width = width * 0.05;
+        let date_width = width * 0.1;
+        let row_height = ui.spacing().interact_size.y;
+        ui.horizontal(|ui| {
+            let mut all_selected =
+                !visible.is_empty() && visible.iter().all(|index| self.selected.contains(index));
+            if ui.checkbox(&mut all_selected, "").changed() {
+                for index in &visible {
+                    if all_selected {
+                        self.selected.insert(*index);
+                    } else {
+                        self.selected.remove(index);
+                    }
+                }
+            }
+            ui.add_sized([password_width, row_height], egui::Label::new("Password"));
+            ui.add_sized([tags_width, row_height], egui::Label::new("Tags"));
+            ui.add_sized([note_width, row_height], egui::Label::new("Note"));
+            ui.add_sized([source_width, row_height], egui::Label::new("Source"));
+            ui.add_sized([hits_width, row_height], egui::Label::new("Hits"));
+            ui.add_sized([date_width, row_height], egui::Label::new("Added"));
+            ui.label("Last used");
+        });
+        ScrollArea::vertical()
+            .auto_shrink(false)
+            .scroll_bar_visibility(ScrollBarVisibility::VisibleWhenNeeded)
+            .show_rows(ui, row_height, visible.len(), |ui, range| {
+                for &index in &visible[range] {
+                    ui.horizontal(|ui| {
+                        let mut selected = self.selected.contains(&index);
+                        if ui.checkbox(&mut selected, "").changed() {
+                            if selected {
+                                self.selected.insert(index);
+                            } else {
+                                self.selected.remove(&index);
+                            }
+                        }
+                        let entry = &mut dictionary.entries[index];
+                        let response = ui.add_sized(
+                            [password_width, row_height],
+                            egui::TextEdit::singleline(entry.password.expose_mut()),
+                        );
+                        if response.changed() {
+                            self.editing_changed = true;
+                        }
+                        if response.lost_focus() && self.editing_changed {
+                            self.editing_changed = false;
+                            committed = true;
+                        }
+                        let mut tags = match &self.editing_tags {
+                            Some((editing, tags)) if *editing == index => tags.clone(),
+                            _ => entry.metadata.tags.join(", "),
+                        };
+                        let response = ui.add_sized(
+                            [tags_width, row_height],
+                            egui::TextEdit::singleline(&mut tags).hint_text("site, uploader"),
+                        );
+                        if response.lost_focus() {
+                            self.editing_tags = None;
+                            let tags = parse_tags(&tags);
+                            if tags != entry.metadata.tags {
+                                entry.metadata.tags = tags;
+                                committed = true;
+                            }
+                        } else if response.has_focus() {
+                            self.editing_tags = Some((index, tags));
+                        }
+                        let response = ui.add_sized(
+                            [note_width, row_height],
+                            egui::TextEdit::singleline(&mut entry.metadata.note),
+                        );
+                        if response.changed() {
+                            self.editing_changed = true;
+                        }
+                        if response.lost_focus() && self.editing_changed {
+                            self.editing_changed = false;
+                            committed = true;
+                        }
+                        ui.add_sized(
+                            [source_width, row_height],
+                            egui::Label::new(&entry.metadata.source).truncate(true),
+                        );
+                        let entry_usage = usage.get(&entry.password);
+                        ui.add_sized(
+                            [hits_width, row_height],
+                            egui::Label::new(entry_usage.map_or(0, |u| u.hits).to_string()),
+                        );
+                        ui.add_sized(
+                            [date_width, row_height],
+                            egui::Label::new(format_date(entry.metadata.added)),
+                        );
+                        ui.label(format_date(entry_usage.and_then(|u| u.last_used)));
+                    });
+                }
+            });
+        committed
+    }
+}
+
+fn parse_tags(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn format_date(date: Option<DateTime<Utc>>) -> String {
+    date.map(|date| date.with_timezone(&Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "—".to_owned())
+}
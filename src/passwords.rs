@@ -0,0 +1,611 @@
+use crate::backup::write_atomic;
+use crate::secret::{Salt, SecretString};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroize;
+
+/// Where an entry came from and what it is for. A plain-text password file
+/// keeps this in a sidecar file next to it, a structured one inline.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EntryMetadata {
+    pub source: String,
+    pub added: Option<DateTime<Utc>>,
+    /// E.g. the site or uploader the password belongs to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl EntryMetadata {
+    pub fn now(source: &str) -> Self {
+        Self {
+            source: source.to_owned(),
+            added: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+}
+
+/// How the password file is stored on disk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DictionaryFormat {
+    /// One password per line, metadata in `<file>.meta.json`.
+    #[default]
+    PlainText,
+    /// A single JSON document with the metadata of every entry.
+    Structured,
+}
+
+impl DictionaryFormat {
+    /// The format a new file at `path` gets.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => DictionaryFormat::Structured,
+            _ => DictionaryFormat::PlainText,
+        }
+    }
+}
+
+const STRUCTURED_MARKER: &str = "wpass-dictionary";
+const STRUCTURED_VERSION: u32 = 1;
+
+/// Layout of a structured dictionary file.
+#[derive(Serialize, Deserialize)]
+struct StructuredFile<E> {
+    format: String,
+    version: u32,
+    entries: E,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum Dedup {
+    Off,
+    CaseSensitive,
+    CaseInsensitive,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum Normalization {
+    Off,
+    Nfc,
+    Nfkc,
+}
+
+/// What sanitizing does to the password file. Blank lines are always dropped.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SanitizeRules {
+    pub trim: bool,
+    /// Comment lines stay in place and the entries between them are sorted separately.
+    pub sort: bool,
+    pub dedup: Dedup,
+    pub normalization: Normalization,
+    /// Lines starting with `#` are kept, but never tried as passwords.
+    pub comments: bool,
+}
+
+impl Default for SanitizeRules {
+    fn default() -> Self {
+        Self {
+            trim: true,
+            sort: true,
+            dedup: Dedup::CaseSensitive,
+            normalization: Normalization::Off,
+            comments: false,
+        }
+    }
+}
+
+impl SanitizeRules {
+    pub fn is_comment(&self, password: &SecretString) -> bool {
+        self.comments && password.expose().trim_start().starts_with('#')
+    }
+
+    fn clean(&self, password: &SecretString) -> SecretString {
+        let password = if self.trim {
+            password.expose().trim()
+        } else {
+            password.expose()
+        };
+        match self.normalization {
+            Normalization::Off => password.into(),
+            Normalization::Nfc => password.nfc().collect::<String>().into(),
+            Normalization::Nfkc => password.nfkc().collect::<String>().into(),
+        }
+    }
+
+    /// How an entry is handed to the extraction backend.
+    fn candidate(&self, password: &SecretString) -> SecretString {
+        match self.trim {
+            true => password.expose().trim().into(),
+            false => password.clone(),
+        }
+    }
+
+    fn dedup_key(&self, password: &SecretString) -> SecretString {
+        match self.dedup {
+            Dedup::CaseInsensitive => password.expose().to_lowercase().into(),
+            Dedup::Off | Dedup::CaseSensitive => password.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PasswordEntry {
+    pub password: SecretString,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
+}
+
+/// The password dictionary, one entry per password in the password file.
+#[derive(PartialEq, Clone, Default)]
+pub struct Dictionary {
+    pub entries: Vec<PasswordEntry>,
+    /// Kept so saving writes the file back the way it was read.
+    pub format: DictionaryFormat,
+}
+
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Dictionary({} entries)", self.entries.len())
+    }
+}
+
+/// Layout of `<file>.meta.json`, keyed by salted password hashes so the
+/// passwords are not written out a second time.
+#[derive(Serialize, Deserialize, Default)]
+struct MetadataSidecar {
+    salt: Salt,
+    entries: HashMap<String, EntryMetadata>,
+}
+
+/// Sidecars written before passwords were hashed are keyed by the passwords.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredMetadata {
+    Hashed(MetadataSidecar),
+    Plain(BTreeMap<SecretString, EntryMetadata>),
+}
+
+/// A file stored next to the password file, e.g. `passwords.txt.meta.json`.
+pub fn sidecar_path_for(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+impl Dictionary {
+    pub fn from_text(text: &str, metadata: &BTreeMap<SecretString, EntryMetadata>) -> Self {
+        let entries = text
+            .lines()
+            .map(SecretString::from)
+            .map(|password| PasswordEntry {
+                metadata: metadata
+                    .get(&password)
+                    .cloned()
+                    .unwrap_or_else(|| EntryMetadata {
+                        source: "File".to_owned(),
+                        ..Default::default()
+                    }),
+                password,
+            })
+            .collect();
+        Self {
+            entries,
+            format: DictionaryFormat::PlainText,
+        }
+    }
+
+    /// Reads either format. `metadata` is only used for plain text.
+    ///
+    /// A plain-text list may well start with a brace, so JSON that does not
+    /// parse falls back to it, unless the file is meant to be structured, by
+    /// `expected` or by its marker. Saving that as plain text would lose all
+    /// of its metadata.
+    pub fn parse(
+        text: &str,
+        metadata: &BTreeMap<SecretString, EntryMetadata>,
+        expected: DictionaryFormat,
+    ) -> Result<Self> {
+        if text.trim_start().starts_with('{') {
+            let structured =
+                expected == DictionaryFormat::Structured || text.contains(STRUCTURED_MARKER);
+            match serde_json::from_str::<StructuredFile<Vec<PasswordEntry>>>(text) {
+                Ok(file) if file.format == STRUCTURED_MARKER => {
+                    return Ok(Self {
+                        entries: file.entries,
+                        format: DictionaryFormat::Structured,
+                    })
+                }
+                Ok(file) if structured => {
+                    return Err(anyhow!("Unknown dictionary format {:?}", file.format))
+                }
+                Err(e) if structured => {
+                    return Err(anyhow!("Malformed structured dictionary: {}", e))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self::from_text(text, metadata))
+    }
+
+    pub fn to_structured(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&StructuredFile {
+            format: STRUCTURED_MARKER.to_owned(),
+            version: STRUCTURED_VERSION,
+            entries: &self.entries,
+        })?)
+    }
+
+    pub fn to_text(&self) -> SecretString {
+        self.entries
+            .iter()
+            .map(|entry| entry.password.expose())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into()
+    }
+
+    pub fn metadata(&self) -> BTreeMap<SecretString, EntryMetadata> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.password.clone(), entry.metadata.clone()))
+            .collect()
+    }
+
+    /// Reads the password file in either format and, for plain text, its
+    /// metadata sidecar if present.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = SecretString::from(fs::read_to_string(path)?);
+        let stored = fs::read_to_string(sidecar_path_for(path, ".meta.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        let metadata = match stored {
+            Some(StoredMetadata::Hashed(sidecar)) => text
+                .expose()
+                .lines()
+                .map(SecretString::from)
+                .filter_map(|password| {
+                    let metadata = sidecar.entries.get(&sidecar.salt.hash(&password))?;
+                    Some((password, metadata.clone()))
+                })
+                .collect(),
+            Some(StoredMetadata::Plain(metadata)) => metadata,
+            None => BTreeMap::new(),
+        };
+        Self::parse(text.expose(), &metadata, DictionaryFormat::for_path(path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if self.format == DictionaryFormat::Structured {
+            let mut json = self.to_structured()?;
+            let written = write_atomic(path, json.as_bytes());
+            json.zeroize();
+            return written;
+        }
+        write_atomic(path, self.to_text().expose().as_bytes())?;
+        let salt = Salt::random();
+        let sidecar = MetadataSidecar {
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| (salt.hash(&entry.password), entry.metadata.clone()))
+                .collect(),
+            salt,
+        };
+        write_atomic(
+            &sidecar_path_for(path, ".meta.json"),
+            serde_json::to_string_pretty(&sidecar)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// The candidates handed to the extraction backend, in file order.
+    pub fn passwords(&self, rules: &SanitizeRules) -> Vec<SecretString> {
+        self.entries
+            .iter()
+            .filter(|entry| !rules.is_comment(&entry.password))
+            .map(|entry| rules.candidate(&entry.password))
+            .filter(|password: &SecretString| !password.is_empty())
+            .collect()
+    }
+
+    pub fn push(&mut self, password: SecretString, source: &str) {
+        self.entries.push(PasswordEntry {
+            password,
+            metadata: EntryMetadata::now(source),
+        });
+    }
+
+    /// Appends the passwords that are not tried already, returning how many were added.
+    pub fn push_new(
+        &mut self,
+        passwords: Vec<SecretString>,
+        source: &str,
+        tags: &[String],
+        rules: &SanitizeRules,
+    ) -> usize {
+        let mut known = self.passwords(rules).into_iter().collect::<HashSet<_>>();
+        let before = self.entries.len();
+        for password in passwords {
+            let candidate = rules.candidate(&password);
+            if candidate.is_empty() || rules.is_comment(&password) || !known.insert(candidate) {
+                continue;
+            }
+            let mut metadata = EntryMetadata::now(source);
+            metadata.tags = tags.to_vec();
+            self.entries.push(PasswordEntry { password, metadata });
+        }
+        self.entries.len() - before
+    }
+
+    /// Appends the entries of `other` that this dictionary does not have yet.
+    pub fn merge_from(&mut self, other: &Dictionary) {
+        let known = self
+            .entries
+            .iter()
+            .map(|entry| entry.password.clone())
+            .collect::<HashSet<_>>();
+        self.entries.extend(
+            other
+                .entries
+                .iter()
+                .filter(|entry| !known.contains(&entry.password))
+                .cloned(),
+        );
+    }
+
+    /// Applies `rules`, keeping the metadata of the first occurrence of a duplicate.
+    pub fn sanitize(&mut self, rules: &SanitizeRules) {
+        for entry in self.entries.iter_mut() {
+            if !rules.is_comment(&entry.password) {
+                entry.password = rules.clean(&entry.password);
+            }
+        }
+        self.entries.retain(|entry| !entry.password.is_empty());
+        if rules.sort {
+            let mut section = 0;
+            let mut keyed = std::mem::take(&mut self.entries)
+                .into_iter()
+                .map(|entry| {
+                    let is_comment = rules.is_comment(&entry.password);
+                    section += usize::from(is_comment);
+                    ((section, !is_comment), entry)
+                })
+                .collect::<Vec<_>>();
+            // Stable sort, so the first occurrence of a duplicate stays first.
+            keyed.sort_by(|(a_key, a), (b_key, b)| {
+                a_key.cmp(b_key).then_with(|| match a_key.1 {
+                    true => a.password.cmp(&b.password),
+                    false => std::cmp::Ordering::Equal,
+                })
+            });
+            self.entries = keyed.into_iter().map(|(_, entry)| entry).collect();
+        }
+        if rules.dedup != Dedup::Off {
+            let mut seen = HashSet::new();
+            self.entries.retain(|entry| {
+                rules.is_comment(&entry.password) || seen.insert(rules.dedup_key(&entry.password))
+            });
+        }
+    }
+}
+
+/// Concatenates the candidates of several dictionaries in priority order,
+/// dropping passwords already contributed by an earlier one.
+pub fn merge_dictionaries<'a>(
+    dictionaries: impl IntoIterator<Item = &'a Dictionary>,
+    rules: &SanitizeRules,
+) -> Vec<SecretString> {
+    let mut seen = HashSet::new();
+    dictionaries
+        .into_iter()
+        .flat_map(|dictionary| dictionary.passwords(rules))
+        .filter(|password| seen.insert(password.clone()))
+        .collect()
+}
+
+/// Moves the passwords of entries tagged with any of `tags` to the front,
+/// keeping the order otherwise.
+pub fn prefer_tagged(
+    mut passwords: Vec<SecretString>,
+    dictionaries: &[&Dictionary],
+    tags: &[String],
+    rules: &SanitizeRules,
+) -> Vec<SecretString> {
+    if tags.is_empty() {
+        return passwords;
+    }
+    let tagged = dictionaries
+        .iter()
+        .flat_map(|dictionary| dictionary.entries.iter())
+        .filter(|entry| {
+            entry
+                .metadata
+                .tags
+                .iter()
+                .any(|tag| tags.iter().any(|wanted| wanted.eq_ignore_ascii_case(tag)))
+        })
+        .map(|entry| rules.candidate(&entry.password))
+        .collect::<HashSet<_>>();
+    passwords.sort_by_key(|password| !tagged.contains(password));
+    passwords
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        merge_dictionaries, prefer_tagged, sidecar_path_for, Dedup, Dictionary, DictionaryFormat,
+        EntryMetadata, SanitizeRules,
+    };
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn should_keep_text_format_and_attach_metadata() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("b".into(), EntryMetadata::now("Manual"));
+        let dictionary = Dictionary::from_text("b\n a \n\nc\n", &metadata);
+        assert_eq!(dictionary.entries.len(), 4);
+        assert_eq!(dictionary.to_text().expose(), "b\n a \n\nc");
+        assert_eq!(dictionary.entries[0].metadata.source, "Manual");
+        assert_eq!(dictionary.entries[3].metadata.source, "File");
+    }
+
+    #[test]
+    fn should_keep_metadata_without_writing_passwords_twice() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("passwords.txt");
+        let mut dictionary = Dictionary::default();
+        dictionary.push("hunter2".into(), "Manual");
+        dictionary.entries[0].metadata.tags = vec!["forum".to_owned()];
+        dictionary.save(&path).unwrap();
+
+        let sidecar = std::fs::read_to_string(sidecar_path_for(&path, ".meta.json")).unwrap();
+        assert!(!sidecar.contains("hunter2"));
+        assert_eq!(Dictionary::load(&path).unwrap(), dictionary);
+    }
+
+    #[test]
+    fn should_push_only_new_passwords() {
+        let mut dictionary = Dictionary::from_text("a\nb", &BTreeMap::new());
+        let added = dictionary.push_new(
+            vec![" a ".into(), "c".into(), "c".into(), "".into()],
+            "Command line",
+            &["forum".to_owned()],
+            &SanitizeRules::default(),
+        );
+        assert_eq!(added, 1);
+        assert_eq!(dictionary.to_text().expose(), "a\nb\nc");
+        assert_eq!(dictionary.entries[2].metadata.tags, vec!["forum"]);
+    }
+
+    #[test]
+    fn should_sanitize_keeping_first_metadata() {
+        let mut dictionary = Dictionary::default();
+        dictionary.push("b".into(), "first");
+        dictionary.push(" a".into(), "Manual");
+        dictionary.push("".into(), "Manual");
+        dictionary.push("b ".into(), "second");
+        dictionary.sanitize(&SanitizeRules::default());
+        assert_eq!(dictionary.to_text().expose(), "a\nb");
+        assert_eq!(dictionary.entries[1].metadata.source, "first");
+    }
+
+    #[test]
+    fn should_merge_in_priority_order_without_duplicates() {
+        let shared = Dictionary::from_text("team\ncommon", &BTreeMap::new());
+        let personal = Dictionary::from_text("mine\n common \nteam", &BTreeMap::new());
+        let merged = merge_dictionaries([&personal, &shared], &SanitizeRules::default());
+        assert_eq!(
+            merged
+                .iter()
+                .map(|password| password.expose())
+                .collect::<Vec<_>>(),
+            vec!["mine", "common", "team"]
+        );
+    }
+
+    #[test]
+    fn should_sanitize_with_rules() {
+        let rules = SanitizeRules {
+            trim: false,
+            sort: true,
+            dedup: Dedup::CaseInsensitive,
+            comments: true,
+            ..Default::default()
+        };
+        let mut dictionary =
+            Dictionary::from_text("# team\nb\n a \nB\n\n# personal\nz\na", &BTreeMap::new());
+        dictionary.sanitize(&rules);
+        assert_eq!(
+            dictionary.to_text().expose(),
+            "# team\n a \nB\n# personal\na\nz"
+        );
+        assert_eq!(
+            dictionary
+                .passwords(&rules)
+                .iter()
+                .map(|password| password.expose())
+                .collect::<Vec<_>>(),
+            vec![" a ", "B", "a", "z"]
+        );
+    }
+
+    #[test]
+    fn should_round_trip_structured_format() {
+        let mut dictionary = Dictionary {
+            format: DictionaryFormat::Structured,
+            ..Default::default()
+        };
+        dictionary.push("hunter2".into(), "Manual");
+        dictionary.entries[0].metadata.tags = vec!["forum".to_owned()];
+        dictionary.entries[0].metadata.note = "from the pinned post".to_owned();
+        let json = dictionary.to_structured().unwrap();
+
+        let parsed =
+            Dictionary::parse(&json, &BTreeMap::new(), DictionaryFormat::PlainText).unwrap();
+        assert_eq!(parsed, dictionary);
+        // Plain text that merely looks like JSON stays plain text.
+        let plain = Dictionary::parse(
+            "{not json\nabc",
+            &BTreeMap::new(),
+            DictionaryFormat::PlainText,
+        )
+        .unwrap();
+        assert_eq!(plain.format, DictionaryFormat::PlainText);
+        assert_eq!(plain.entries.len(), 2);
+    }
+
+    #[test]
+    fn should_refuse_malformed_structured_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let json = temp.path().join("passwords.json");
+        std::fs::write(&json, "{\"entries\": [{\"password\": \"hunter2\",}]}").unwrap();
+        assert!(Dictionary::load(&json).is_err());
+
+        // Marked as ours, so not taken for plain text whatever the extension.
+        let txt = temp.path().join("passwords.txt");
+        std::fs::write(&txt, "{\"format\": \"wpass-dictionary\", \"version\": 1,").unwrap();
+        assert!(Dictionary::load(&txt).is_err());
+    }
+
+    #[test]
+    fn should_prefer_tagged_passwords() {
+        let mut dictionary = Dictionary::from_text("a\nb\nc", &BTreeMap::new());
+        dictionary.entries[2].metadata.tags = vec!["Alice".to_owned()];
+        let rules = SanitizeRules::default();
+        let ordered = prefer_tagged(
+            dictionary.passwords(&rules),
+            &[&dictionary],
+            &["alice".to_owned()],
+            &rules,
+        );
+        assert_eq!(
+            ordered
+                .iter()
+                .map(|password| password.expose())
+                .collect::<Vec<_>>(),
+            vec!["c", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn should_merge_entries_missing_locally() {
+        let mut local = Dictionary::from_text("a\nb", &BTreeMap::new());
+        let mut theirs = Dictionary::default();
+        theirs.push("c".into(), "Manual");
+        theirs.push("a".into(), "Manual");
+        local.merge_from(&theirs);
+        assert_eq!(local.to_text().expose(), "a\nb\nc");
+        assert_eq!(local.entries[0].metadata.source, "File");
+        assert_eq!(local.entries[2].metadata.source, "Manual");
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const SEVEN_ZIP_NAMES: &[&str] = &["7z", "7zz", "7za"];
+/// Places 7-Zip is usually installed to that are not always on `PATH`.
+const SEVEN_ZIP_LOCATIONS: &[&str] = &[
+    "/usr/bin",
+    "/usr/local/bin",
+    "/usr/lib/p7zip",
+    "/opt/homebrew/bin",
+    r"C:\Program Files\7-Zip",
+    r"C:\Program Files (x86)\7-Zip",
+];
+
+/// What a 7z executable told us about itself.
+#[derive(Debug, Clone)]
+pub struct SevenZipInfo {
+    pub version: String,
+    pub formats: Vec<String>,
+}
+
+/// Looks for a 7z executable on `PATH` first, then in the usual install locations.
+pub fn find_seven_zip() -> Option<PathBuf> {
+    let path_dirs = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    path_dirs
+        .into_iter()
+        .chain(SEVEN_ZIP_LOCATIONS.iter().map(PathBuf::from))
+        .flat_map(|dir| {
+            SEVEN_ZIP_NAMES
+                .iter()
+                .map(move |name| dir.join(format!("{}{}", name, env::consts::EXE_SUFFIX)))
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+/// Where `executable` is: a bare name is looked up on `PATH`, the way running
+/// it would. `None` if it is not there.
+pub fn resolve_executable(executable: &Path) -> Option<PathBuf> {
+    if executable.components().count() > 1 {
+        return executable.is_file().then(|| executable.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| {
+            [
+                dir.join(executable),
+                dir.join(format!(
+                    "{}{}",
+                    executable.display(),
+                    env::consts::EXE_SUFFIX
+                )),
+            ]
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+/// Runs `7z i` and reads the version and the supported formats from its output.
+pub fn probe_seven_zip(executable: &str) -> Result<SevenZipInfo> {
+    if executable.is_empty() {
+        return Err(anyhow!("No executable set"));
+    }
+    let output = Command::new(executable)
+        .arg("i")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!("Exited with {}", output.status));
+    }
+    parse_seven_zip_info(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_seven_zip_info(output: &str) -> Result<SevenZipInfo> {
+    let version = output
+        .lines()
+        .find_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("7-Zip") {
+                return None;
+            }
+            // Skip markers such as "[64]" or "(a)" in front of the version number.
+            words.find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .ok_or_else(|| anyhow!("Not a 7-Zip executable"))?
+        .to_owned();
+    let formats = output
+        .lines()
+        .skip_while(|line| line.trim() != "Formats:")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            // The format name follows the column of capability flags, e.g. "C...F..........c.a.m+".
+            words.find(|word| word.len() >= 8 && word.matches('.').count() >= 3)?;
+            words.next().map(str::to_owned)
+        })
+        .collect();
+    Ok(SevenZipInfo { version, formats })
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_seven_zip_info;
+
+    #[test]
+    fn should_parse_version_and_formats() {
+        let output = "
+7-Zip [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+p7zip Version 16.02 (locale=C.UTF-8,Utf16=on,HugeFiles=on,64 bits,4 CPUs)
+
+Libs:
+ 0  /usr/lib/p7zip/7z.so
+
+Formats:
+ 0 C...F..........c.a.m+ 7z       7z            7z'..
+ 0  ....M...........  Rar      rar r00       Rar!..
+ 0 C...............   zip      zip z01 zipx  PK..
+
+Codecs:
+ 0 4ED   303011B BCJ2
+";
+        let info = parse_seven_zip_info(output).unwrap();
+        assert_eq!(info.version, "16.02");
+        assert_eq!(info.formats, vec!["7z", "Rar", "zip"]);
+    }
+
+    #[test]
+    fn should_reject_other_programs() {
+        assert!(parse_seven_zip_info("GNU tar 1.34\n").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum PatternKind {
+    Glob,
+    Regex,
+}
+
+impl PatternKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PatternKind::Glob => "Glob",
+            PatternKind::Regex => "Regex",
+        }
+    }
+}
+
+/// Matched against the whole archive path, ignoring case.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PathPattern {
+    pub kind: PatternKind,
+    pub pattern: String,
+    /// Compiled on first use and again once `kind` or `pattern` changed.
+    #[serde(skip)]
+    compiled: Mutex<Option<Compiled>>,
+}
+
+#[derive(Debug)]
+struct Compiled {
+    kind: PatternKind,
+    pattern: String,
+    matcher: Result<Matcher, String>,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Glob(globset::GlobMatcher),
+    Regex(Regex),
+}
+
+impl Clone for PathPattern {
+    fn clone(&self) -> Self {
+        Self::new(self.kind, self.pattern.clone())
+    }
+}
+
+impl PartialEq for PathPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.pattern == other.pattern
+    }
+}
+
+impl PathPattern {
+    pub fn new(kind: PatternKind, pattern: String) -> Self {
+        Self {
+            kind,
+            pattern,
+            compiled: Mutex::default(),
+        }
+    }
+
+    fn matcher(&self) -> Result<Matcher, String> {
+        let mut compiled = self.compiled.lock().unwrap_or_else(|e| e.into_inner());
+        let current = matches!(&*compiled, Some(compiled)
+            if compiled.kind == self.kind && compiled.pattern == self.pattern);
+        if !current {
+            *compiled = Some(Compiled {
+                kind: self.kind,
+                pattern: self.pattern.clone(),
+                matcher: self.compile(),
+            });
+        }
+        compiled.as_ref().unwrap().matcher.clone()
+    }
+
+    fn compile(&self) -> Result<Matcher, String> {
+        match self.kind {
+            PatternKind::Glob => globset::GlobBuilder::new(&self.pattern)
+                .case_insensitive(true)
+                .build()
+                .map(|glob| Matcher::Glob(glob.compile_matcher()))
+                .map_err(|e| e.to_string()),
+            PatternKind::Regex => Regex::new(&format!("(?i){}", self.pattern))
+                .map(Matcher::Regex)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Named regex groups of a match, usable in destination templates.
+    pub fn matches(&self, path: &Path) -> Result<Option<BTreeMap<String, String>>> {
+        let text = path.to_string_lossy();
+        match self.matcher().map_err(anyhow::Error::msg)? {
+            Matcher::Glob(matcher) => Ok(matcher.is_match(path).then(BTreeMap::new)),
+            Matcher::Regex(regex) => Ok(regex.captures(&text).map(|captures| {
+                regex
+                    .capture_names()
+                    .flatten()
+                    .filter_map(|name| {
+                        let value = captures.name(name)?.as_str().to_owned();
+                        Some((name.to_owned(), value))
+                    })
+                    .collect()
+            })),
+        }
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.matcher().err()
+    }
+}
+
+/// A captured value as one path component: it comes from the archive's name,
+/// which must not be able to steer the destination elsewhere.
+fn path_component(value: &str) -> String {
+    let component = value.replace(['/', '\\'], "_");
+    match component.as_str() {
+        "." | ".." => "_".to_owned(),
+        _ => component,
+    }
+}
+
+/// Replaces `{dir}`, `{stem}`, `{name}` and named regex groups; unknown
+/// placeholders are left as they are.
+fn expand_template(template: &str, path: &Path, captures: &BTreeMap<String, String>) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER
+        .get_or_init(|| Regex::new(r"\{(\w+)\}").unwrap())
+        .replace_all(template, |found: &regex::Captures| {
+            let lossy = |part: Option<&std::ffi::OsStr>| {
+                part.map(|part| part.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+            match &found[1] {
+                "dir" => lossy(path.parent().map(Path::as_os_str)),
+                "stem" => lossy(path.file_stem()),
+                "name" => lossy(path.file_name()),
+                name => captures
+                    .get(name)
+                    .map(|value| path_component(value))
+                    .unwrap_or_else(|| found[0].to_owned()),
+            }
+        })
+        .into_owned()
+}
+
+pub fn expand_destination(
+    template: &str,
+    path: &Path,
+    captures: &BTreeMap<String, String>,
+) -> PathBuf {
+    PathBuf::from(expand_template(template, path, captures))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expand_template, PathPattern, PatternKind};
+    use std::path::Path;
+
+    #[test]
+    fn should_match_globs_and_regexes() {
+        let path = Path::new("/downloads/Alice/pack [alice].rar");
+        let glob = PathPattern::new(PatternKind::Glob, "**/alice/*.rar".to_owned());
+        assert!(glob.matches(path).unwrap().is_some());
+
+        let regex = PathPattern::new(PatternKind::Regex, r"\[(?P<uploader>\w+)\]".to_owned());
+        let captures = regex.matches(path).unwrap().unwrap();
+        assert_eq!(captures["uploader"], "alice");
+        assert_eq!(
+            expand_template("/sorted/{uploader}/{stem}/{missing}", path, &captures),
+            "/sorted/alice/pack [alice]/{missing}"
+        );
+
+        let escaping = PathPattern::new(PatternKind::Regex, r"^/in/(?P<rest>.*)\.rar$".to_owned());
+        let captures = escaping
+            .matches(Path::new("/in/../../etc.rar"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            expand_template("/out/{rest}", path, &captures),
+            "/out/.._.._etc"
+        );
+        let captures = escaping.matches(Path::new("/in/...rar")).unwrap().unwrap();
+        assert_eq!(expand_template("/out/{rest}", path, &captures), "/out/_");
+
+        let mut invalid = PathPattern::new(PatternKind::Regex, "(".to_owned());
+        assert!(invalid.error().is_some());
+        // Edits are picked up even though the pattern was compiled before.
+        invalid.pattern = "x".to_owned();
+        assert!(invalid.error().is_none());
+    }
+}
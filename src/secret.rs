@@ -0,0 +1,118 @@
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A password or other secret text.
+///
+/// `Debug` never shows the contents, so secrets can't end up in logs by
+/// accident, and the memory is wiped when the value is dropped.
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// For widgets that edit the text in place.
+    pub fn expose_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_owned())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Hashes secrets for files kept next to the password file, so they can be
+/// recognised without being stored. Each file gets its own random salt.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Salt(String);
+
+impl Salt {
+    pub fn random() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self(salt.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn hash(&self, secret: &SecretString) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.0.as_bytes());
+        hasher.update(secret.expose().as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+impl Default for Salt {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+impl fmt::Debug for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Salt(..)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Salt, SecretString};
+
+    #[test]
+    fn should_redact_debug_output() {
+        let secret = SecretString::from("hunter2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert!(!format!("{:?}", vec![secret]).contains("hunter2"));
+    }
+
+    #[test]
+    fn should_hash_with_salt() {
+        let salt = Salt::random();
+        let hash = salt.hash(&"hunter2".into());
+        assert_eq!(hash, salt.hash(&"hunter2".into()));
+        assert!(!hash.contains("hunter2"));
+        assert_ne!(hash, Salt::random().hash(&"hunter2".into()));
+    }
+}
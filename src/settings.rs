@@ -0,0 +1,56 @@
+use crate::backup::write_atomic;
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The window title, which eframe also stores the persisted state under.
+pub const APP_NAME: &str = "Wpass GUI";
+
+/// eframe keeps all persisted values in one RON map.
+fn settings_file() -> Option<PathBuf> {
+    eframe::storage_dir(APP_NAME).map(|dir| dir.join("app.ron"))
+}
+
+/// Empty if nothing was saved yet. A file that cannot be read is an error,
+/// so it is not replaced by the defaults.
+fn read_values() -> Result<HashMap<String, String>> {
+    let Some(path) = settings_file() else {
+        return Ok(HashMap::new());
+    };
+    let ron = match fs::read_to_string(&path) {
+        Ok(ron) => ron,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(anyhow!("Could not read the settings {:?}: {}", path, e)),
+    };
+    ron::from_str(&ron).map_err(|e| anyhow!("Could not parse the settings {:?}: {}", path, e))
+}
+
+/// Reads what the window persisted, the way `eframe::get_value` does, for
+/// use without a window. `None` if nothing was saved yet.
+pub fn load<T: DeserializeOwned>() -> Result<Option<T>> {
+    read_values()?
+        .get(eframe::APP_KEY)
+        .map(|value| {
+            ron::from_str(value).map_err(|e| anyhow!("Could not parse the settings: {}", e))
+        })
+        .transpose()
+}
+
+/// Writes `value` where the window reads it on its next start. A window that
+/// is open at the same time overwrites it when it closes.
+pub fn save<T: Serialize>(value: &T) -> Result<()> {
+    let path = settings_file().ok_or_else(|| anyhow!("No directory to keep the settings in"))?;
+    let mut values = read_values()?;
+    values.insert(eframe::APP_KEY.to_owned(), ron::ser::to_string(value)?);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(
+        &path,
+        ron::ser::to_string_pretty(&values, Default::default())?.as_bytes(),
+    )
+}
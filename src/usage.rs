@@ -0,0 +1,156 @@
+use crate::backup::write_atomic;
+use crate::secret::{Salt, SecretString};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The order in which dictionary entries are tried against an archive.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub enum TrialOrder {
+    FileOrder,
+    MostSuccessful,
+    MostRecent,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct PasswordUsage {
+    pub hits: u64,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// How often each password opened an archive, kept in a sidecar file next to
+/// the password file. Passwords are keyed by their salted hash.
+#[derive(Default, Serialize, Deserialize, Clone)]
+#[serde(from = "StoredUsage")]
+pub struct UsageStore {
+    salt: Salt,
+    usage: HashMap<String, PasswordUsage>,
+}
+
+/// Stores written before passwords were hashed are keyed by the passwords.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredUsage {
+    Hashed {
+        salt: Salt,
+        usage: HashMap<String, PasswordUsage>,
+    },
+    Plain(HashMap<SecretString, PasswordUsage>),
+}
+
+impl From<StoredUsage> for UsageStore {
+    fn from(stored: StoredUsage) -> Self {
+        match stored {
+            StoredUsage::Hashed { salt, usage } => Self { salt, usage },
+            StoredUsage::Plain(plain) => {
+                let salt = Salt::random();
+                let usage = plain
+                    .into_iter()
+                    .map(|(password, usage)| (salt.hash(&password), usage))
+                    .collect();
+                Self { salt, usage }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for UsageStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UsageStore({} passwords)", self.usage.len())
+    }
+}
+
+impl UsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A missing or unreadable store simply starts out empty.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn get(&self, password: &SecretString) -> Option<&PasswordUsage> {
+        self.usage.get(&self.salt.hash(password))
+    }
+
+    pub fn record(&mut self, password: &SecretString) {
+        let usage = self.usage.entry(self.salt.hash(password)).or_default();
+        usage.hits += 1;
+        usage.last_used = Some(Utc::now());
+    }
+
+    /// Reorders `passwords`; ties keep their file order.
+    pub fn order(&self, mut passwords: Vec<SecretString>, order: TrialOrder) -> Vec<SecretString> {
+        match order {
+            TrialOrder::FileOrder => {}
+            // Cached, hashing every password for each comparison would be slow.
+            TrialOrder::MostSuccessful => passwords.sort_by_cached_key(|password| {
+                Reverse(self.get(password).map_or(0, |usage| usage.hits))
+            }),
+            TrialOrder::MostRecent => passwords.sort_by_cached_key(|password| {
+                Reverse(self.get(password).and_then(|usage| usage.last_used))
+            }),
+        }
+        passwords
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TrialOrder, UsageStore};
+
+    #[test]
+    fn should_order_by_usage() {
+        let mut store = UsageStore::new();
+        store.record(&"often".into());
+        store.record(&"often".into());
+        store.record(&"recent".into());
+        let passwords = vec!["never".into(), "recent".into(), "often".into()];
+        let order = |order| {
+            store
+                .order(passwords.clone(), order)
+                .iter()
+                .map(|password| password.expose().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            order(TrialOrder::FileOrder),
+            vec!["never", "recent", "often"]
+        );
+        assert_eq!(
+            order(TrialOrder::MostSuccessful),
+            vec!["often", "recent", "never"]
+        );
+        assert_eq!(
+            order(TrialOrder::MostRecent),
+            vec!["recent", "often", "never"]
+        );
+    }
+
+    #[test]
+    fn should_hash_passwords_of_old_stores() {
+        let old = r#"{"hunter2": {"hits": 3, "last_used": null}}"#;
+        let store: UsageStore = serde_json::from_str(old).unwrap();
+        assert_eq!(store.get(&"hunter2".into()).unwrap().hits, 3);
+
+        let saved = serde_json::to_string(&store).unwrap();
+        assert!(!saved.contains("hunter2"));
+        let reloaded: UsageStore = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded.get(&"hunter2".into()).unwrap().hits, 3);
+    }
+}
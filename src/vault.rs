@@ -0,0 +1,247 @@
+use crate::fingerprint::ArchiveIndex;
+use crate::passwords::EntryMetadata;
+use crate::secret::SecretString;
+use crate::usage::UsageStore;
+use anyhow::{anyhow, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// Vault layout: magic, Argon2id costs, salt, nonce, then the XChaCha20-Poly1305
+/// ciphertext. Everything before the ciphertext is authenticated as associated data.
+const MAGIC: &[u8; 8] = b"WPASSVLT";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN;
+/// The costs a vault may ask for, so a crafted header cannot make unlocking
+/// allocate or compute without end. Memory is in KiB, the cap is 1 GiB.
+const MAX_M_COST: u32 = 1 << 20;
+const MAX_T_COST: u32 = 64;
+const MAX_P_COST: u32 = 16;
+
+/// Argon2id cost parameters, stored in the vault so they can be raised later.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Everything that would otherwise be stored in plaintext next to the password file.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct VaultContents {
+    pub passwords: SecretString,
+    pub metadata: BTreeMap<SecretString, EntryMetadata>,
+    pub usage: UsageStore,
+    /// Missing from vaults sealed before archives were remembered.
+    #[serde(default)]
+    pub archives: ArchiveIndex,
+}
+
+/// The key derived from the master password, kept so saving does not need the
+/// (slow) key derivation again.
+pub struct VaultKey {
+    key: [u8; 32],
+    salt: [u8; SALT_LEN],
+    params: KdfParams,
+}
+
+impl fmt::Debug for VaultKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultKey")
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for VaultKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl VaultKey {
+    /// Derives a key for a new vault, with a fresh salt.
+    pub fn create(master_password: &SecretString, params: KdfParams) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(master_password, salt, params)
+    }
+
+    fn derive(
+        master_password: &SecretString,
+        salt: [u8; SALT_LEN],
+        params: KdfParams,
+    ) -> Result<Self> {
+        let argon2 = Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+                .map_err(|e| anyhow!("Invalid key derivation parameters: {}", e))?,
+        );
+        let mut key = [0u8; 32];
+        argon2
+            .hash_password_into(master_password.expose().as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+        Ok(Self { key, salt, params })
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        header.extend_from_slice(&self.params.m_cost.to_le_bytes());
+        header.extend_from_slice(&self.params.t_cost.to_le_bytes());
+        header.extend_from_slice(&self.params.p_cost.to_le_bytes());
+        header.extend_from_slice(&self.salt);
+        header
+    }
+
+    pub fn seal(&self, contents: &VaultContents) -> Result<Vec<u8>> {
+        let cipher = XChaCha20Poly1305::new(&self.key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let header = self.header();
+        let mut plaintext = serde_json::to_vec(contents)?;
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| anyhow!("Encryption failed"));
+        plaintext.zeroize();
+        let ciphertext = ciphertext?;
+        let mut sealed = header;
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Derives the key from `master_password` and decrypts the vault with it.
+    pub fn open(master_password: &SecretString, sealed: &[u8]) -> Result<(Self, VaultContents)> {
+        let (params, salt) = Self::parse_header(sealed)?;
+        let key = Self::derive(master_password, salt, params)?;
+        let contents = key.reopen(sealed)?;
+        Ok((key, contents))
+    }
+
+    /// Decrypts a vault sealed with this key, e.g. after it changed on disk.
+    pub fn reopen(&self, sealed: &[u8]) -> Result<VaultContents> {
+        let header = self.header();
+        if sealed.len() < HEADER_LEN + NONCE_LEN || !sealed.starts_with(&header) {
+            return Err(anyhow!(
+                "The vault was sealed with a different master password"
+            ));
+        }
+        let (nonce, ciphertext) = sealed[HEADER_LEN..].split_at(NONCE_LEN);
+        let mut plaintext = XChaCha20Poly1305::new(&self.key.into())
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| anyhow!("Wrong master password or damaged vault"))?;
+        let contents = serde_json::from_slice(&plaintext);
+        plaintext.zeroize();
+        Ok(contents?)
+    }
+
+    fn parse_header(sealed: &[u8]) -> Result<(KdfParams, [u8; SALT_LEN])> {
+        if sealed.len() < HEADER_LEN + NONCE_LEN || !sealed.starts_with(MAGIC) {
+            return Err(anyhow!("Not a password vault"));
+        }
+        if sealed[MAGIC.len()] != VERSION {
+            return Err(anyhow!("Unsupported vault version {}", sealed[MAGIC.len()]));
+        }
+        let read_u32 =
+            |offset: usize| u32::from_le_bytes(sealed[offset..offset + 4].try_into().unwrap());
+        let costs = MAGIC.len() + 1;
+        let params = KdfParams {
+            m_cost: read_u32(costs),
+            t_cost: read_u32(costs + 4),
+            p_cost: read_u32(costs + 8),
+        };
+        if params.m_cost > MAX_M_COST || params.t_cost > MAX_T_COST || params.p_cost > MAX_P_COST {
+            return Err(anyhow!(
+                "The vault asks for more key derivation work than allowed: {:?}",
+                params
+            ));
+        }
+        let salt = sealed[costs + 12..HEADER_LEN].try_into().unwrap();
+        Ok((params, salt))
+    }
+}
+
+/// Whether the file at `path` starts like a vault.
+pub fn is_vault(path: &Path) -> bool {
+    let mut magic = [0u8; MAGIC.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| &magic == MAGIC)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KdfParams, VaultContents, VaultKey};
+
+    // Keep the tests fast; the defaults are meant to be slow.
+    const CHEAP: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn should_round_trip_with_master_password() {
+        let key = VaultKey::create(&"correct horse".into(), CHEAP).unwrap();
+        let contents = VaultContents {
+            passwords: "a\nb".into(),
+            ..Default::default()
+        };
+        let sealed = key.seal(&contents).unwrap();
+        assert!(!sealed.windows(3).any(|window| window == b"a\nb"));
+
+        let (_, opened) = VaultKey::open(&"correct horse".into(), &sealed).unwrap();
+        assert_eq!(opened.passwords.expose(), "a\nb");
+        assert!(VaultKey::open(&"wrong horse".into(), &sealed).is_err());
+    }
+
+    #[test]
+    fn should_reject_tampered_header() {
+        let key = VaultKey::create(&"correct horse".into(), CHEAP).unwrap();
+        let mut sealed = key.seal(&VaultContents::default()).unwrap();
+        // Flip a bit in the salt.
+        sealed[30] ^= 1;
+        assert!(VaultKey::open(&"correct horse".into(), &sealed).is_err());
+
+        // Costs beyond the caps are refused before any key derivation.
+        let mut sealed = key.seal(&VaultContents::default()).unwrap();
+        sealed[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = VaultKey::open(&"correct horse".into(), &sealed).unwrap_err();
+        assert!(error.to_string().contains("key derivation"));
+    }
+}
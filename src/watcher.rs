@@ -0,0 +1,162 @@
+use crate::archive::is_archive;
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How often the watched directories are scanned.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long the size of a new file must stay unchanged before we consider it complete.
+pub const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+#[derive(Debug)]
+enum FileState {
+    Settling { size: u64, since: Instant },
+    Done,
+}
+
+/// Polls a set of directories and reports archives that appeared after the
+/// first scan, once they stopped growing.
+#[derive(Debug, Default)]
+pub struct FolderWatcher {
+    last_poll: Option<Instant>,
+    primed: HashSet<PathBuf>,
+    files: HashMap<PathBuf, FileState>,
+}
+
+impl FolderWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scans `dirs` if `POLL_INTERVAL` has passed since the last scan.
+    /// Returns the index of the directory together with each archive that is ready.
+    pub fn poll(&mut self, dirs: &[PathBuf], now: Instant) -> Vec<(usize, PathBuf)> {
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < POLL_INTERVAL {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+        let mut ready = Vec::new();
+        // Files of directories that cannot be read right now are kept as they are.
+        let mut keep = HashSet::new();
+        for (index, dir) in dirs.iter().enumerate() {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    debug!("Cannot read watched directory {:?}: {}", dir, e);
+                    keep.extend(
+                        self.files
+                            .keys()
+                            .filter(|path| path.parent() == Some(dir.as_path()))
+                            .cloned(),
+                    );
+                    continue;
+                }
+            };
+            // Whatever is already there when we first look is not "new".
+            let primed = self.primed.contains(dir);
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let size = match entry.metadata() {
+                    Ok(metadata) if metadata.is_file() => metadata.len(),
+                    _ => continue,
+                };
+                if !is_archive(&path) {
+                    continue;
+                }
+                keep.insert(path.clone());
+                if !primed {
+                    self.files.insert(path, FileState::Done);
+                    continue;
+                }
+                match self.files.get_mut(&path) {
+                    None => {
+                        debug!("New file {:?} in watched directory", path);
+                        self.files
+                            .insert(path, FileState::Settling { size, since: now });
+                    }
+                    Some(FileState::Settling {
+                        size: last_size,
+                        since,
+                    }) => {
+                        if *last_size != size {
+                            *last_size = size;
+                            *since = now;
+                        } else if now.duration_since(*since) >= SETTLE_TIME {
+                            self.files.insert(path.clone(), FileState::Done);
+                            ready.push((index, path));
+                        }
+                    }
+                    Some(FileState::Done) => {}
+                }
+            }
+            self.primed.insert(dir.clone());
+        }
+        // Forget deleted files, so one downloaded again under the same name is new.
+        self.files.retain(|path, _| keep.contains(path));
+        // A folder that is watched again later starts over with a first scan.
+        self.primed.retain(|dir| dirs.contains(dir));
+        ready
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FolderWatcher, POLL_INTERVAL, SETTLE_TIME};
+    use std::time::Instant;
+
+    #[test]
+    fn should_report_new_archive_once_settled() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("old.zip"), b"old").unwrap();
+        let dirs = vec![dir.to_path_buf()];
+        let mut watcher = FolderWatcher::new();
+        let start = Instant::now();
+
+        assert!(watcher.poll(&dirs, start).is_empty());
+        std::fs::write(dir.join("new.zip"), b"new").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"not an archive").unwrap();
+        let seen = start + POLL_INTERVAL;
+        assert!(watcher.poll(&dirs, seen).is_empty());
+        // Still growing, so the settle timer restarts.
+        std::fs::write(dir.join("new.zip"), b"new and longer").unwrap();
+        let grown = seen + SETTLE_TIME;
+        assert!(watcher.poll(&dirs, grown).is_empty());
+        let settled = grown + SETTLE_TIME;
+        assert_eq!(watcher.poll(&dirs, settled), vec![(0, dir.join("new.zip"))]);
+        let done = settled + SETTLE_TIME;
+        assert!(watcher.poll(&dirs, done).is_empty());
+
+        std::fs::remove_file(dir.join("new.zip")).unwrap();
+        assert!(watcher.poll(&dirs, done + POLL_INTERVAL).is_empty());
+        assert_eq!(watcher.files.len(), 1);
+        std::fs::write(dir.join("new.zip"), b"again").unwrap();
+        let downloaded = done + 2 * POLL_INTERVAL;
+        assert!(watcher.poll(&dirs, downloaded).is_empty());
+        assert_eq!(
+            watcher.poll(&dirs, downloaded + SETTLE_TIME),
+            vec![(0, dir.join("new.zip"))]
+        );
+    }
+
+    #[test]
+    fn should_not_report_existing_archives_of_re_added_folder() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let dirs = vec![dir.to_path_buf()];
+        let mut watcher = FolderWatcher::new();
+        let start = Instant::now();
+
+        assert!(watcher.poll(&dirs, start).is_empty());
+        let removed = start + POLL_INTERVAL;
+        assert!(watcher.poll(&[], removed).is_empty());
+        std::fs::write(dir.join("while-unwatched.zip"), b"old").unwrap();
+        let added = removed + POLL_INTERVAL;
+        assert!(watcher.poll(&dirs, added).is_empty());
+        assert!(watcher.poll(&dirs, added + SETTLE_TIME).is_empty());
+    }
+}
@@ -33,15 +33,67 @@ impl<T: Send + 'static> Task<T> {
             task: rx,
         }
     }
-    fn ui(&self, ui: &mut egui::Ui) {
+    /// `None` while the task is still running.
+    pub fn result(&self) -> Option<Result<&T, &anyhow::Error>> {
+        match &self.state {
+            TaskState::Running => None,
+            TaskState::Finished(value) => Some(Ok(value)),
+            TaskState::Failed(error) => Some(Err(error)),
+        }
+    }
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        index: usize,
+        finished_ui: &mut impl FnMut(&mut egui::Ui, &mut T),
+    ) {
         ui.label(self.description.clone());
-        ui.centered_and_justified(|ui| {
-            ui.label(match &self.state {
-                TaskState::Running => "...".to_string(),
-                TaskState::Finished(_) => "√".to_string(),
-                TaskState::Failed(e) => e.to_string(),
-            });
-        });
+        match &mut self.state {
+            TaskState::Finished(value) => {
+                // Keeps the ids of widgets added by `finished_ui` apart between rows.
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("√");
+                        finished_ui(ui, value);
+                    });
+                });
+            }
+            state => {
+                ui.centered_and_justified(|ui| {
+                    ui.label(match state {
+                        TaskState::Failed(e) => e.to_string(),
+                        _ => "...".to_string(),
+                    });
+                });
+            }
+        }
+    }
+}
+
+impl<T: Send + Debug + 'static> Task<T> {
+    /// Picks up the result of the task. Returns true if it finished just now.
+    pub fn poll(&mut self) -> bool {
+        if !matches!(self.state, TaskState::Running) {
+            return false;
+        }
+        match self.task.try_recv() {
+            Ok(result) => {
+                debug!(
+                    "Task {} finished with result {:?}",
+                    self.description, result
+                );
+                self.state = match result {
+                    Ok(value) => TaskState::Finished(value),
+                    Err(error) => TaskState::Failed(error),
+                };
+                true
+            }
+            Err(mpsc::TryRecvError::Empty) => false,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.state = TaskState::Failed(anyhow!("Task channel disconnected"));
+                true
+            }
+        }
     }
 }
 
@@ -58,35 +110,24 @@ impl<T: Send + Debug + 'static> Showcase<T> {
     pub fn new() -> Self {
         Self { tasks: Vec::new() }
     }
-    pub fn poll(&mut self) {
-        for task in self.tasks.iter_mut() {
-            match task.state {
-                TaskState::Running => match task.task.try_recv() {
-                    Ok(result) => {
-                        debug!(
-                            "Task {} finished with result {:?}",
-                            task.description, result
-                        );
-                        match result {
-                            Ok(value) => {
-                                task.state = TaskState::Finished(value);
-                            }
-                            Err(error) => {
-                                task.state = TaskState::Failed(error);
-                            }
-                        }
-                    }
-                    Err(mpsc::TryRecvError::Empty) => {}
-                    Err(mpsc::TryRecvError::Disconnected) => {
-                        task.state = TaskState::Failed(anyhow!("Task channel disconnected"));
-                    }
-                },
-                TaskState::Finished(_) => {}
-                TaskState::Failed(_) => {}
-            }
-        }
+    /// Returns the results of the tasks that finished since the last poll.
+    pub fn poll(&mut self) -> Vec<&T> {
+        let finished = self
+            .tasks
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, task)| task.poll().then_some(index))
+            .collect::<Vec<_>>();
+        finished
+            .into_iter()
+            .filter_map(|index| match &self.tasks[index].state {
+                TaskState::Finished(value) => Some(value),
+                _ => None,
+            })
+            .collect()
     }
-    pub fn ui(&mut self, ui: &mut egui::Ui) {
+    /// `finished_ui` adds actions to the rows of finished tasks.
+    pub fn ui(&mut self, ui: &mut egui::Ui, mut finished_ui: impl FnMut(&mut egui::Ui, &mut T)) {
         ScrollArea::vertical()
             .auto_shrink(false)
             .scroll_bar_visibility(ScrollBarVisibility::VisibleWhenNeeded)
@@ -97,8 +138,8 @@ impl<T: Send + Debug + 'static> Showcase<T> {
                     .max_col_width(ui.available_width() / 2.0)
                     .striped(true)
                     .show(ui, |ui| {
-                        for task in self.tasks.iter() {
-                            task.ui(ui);
+                        for (index, task) in self.tasks.iter_mut().enumerate() {
+                            task.ui(ui, index, &mut finished_ui);
                             ui.end_row();
                         }
                     });